@@ -0,0 +1,105 @@
+use std::sync::LazyLock;
+
+use crate::{
+  Command, CommandAction, Error, ObjectDefinition, ObjectKind, ObjectManifest, ObjectName, ObjectRef, Result
+};
+
+static NAME: LazyLock<ObjectName> = LazyLock::new(|| "batch".into());
+
+/// Batch
+///
+/// Inserts and removals applied by the engine all at once: if one of them
+/// fails, none of them is applied.
+pub struct Batch {
+  command: Command,
+  actions: Vec<CommandAction>,
+  error: Option<Error>,
+}
+
+impl Batch {
+  /// Kind reported by batches, e.g. in metrics and spans, as their steps
+  /// may span several kinds.
+  pub const KIND: ObjectKind = "batch";
+
+  /// Name reported by batches, as their steps may span several objects.
+  pub fn name() -> &'static ObjectName {
+    &NAME
+  }
+
+  pub fn new(command: Command) -> Self {
+    Self {
+      command,
+      actions: Vec::new(),
+      error: None,
+    }
+  }
+
+  pub fn insert<O>(&mut self, manifest: ObjectManifest<O>) -> &mut Self
+  where
+    O: ObjectDefinition,
+  {
+    self.actions.push(CommandAction::InsertManifest(
+      O::kind(),
+      Box::new(manifest),
+      None,
+    ));
+    self
+  }
+
+  pub fn insert_owned<O>(
+    &mut self,
+    owner: ObjectRef,
+    manifest: ObjectManifest<O>,
+  ) -> &mut Self
+  where
+    O: ObjectDefinition,
+  {
+    if owner == manifest.object_ref() {
+      self.error.get_or_insert(Error::Conflict {
+        name: owner.name,
+        reason: "an object can't own itself".into(),
+      });
+      return self;
+    }
+
+    self.actions.push(CommandAction::InsertManifest(
+      O::kind(),
+      Box::new(manifest),
+      Some(owner),
+    ));
+    self
+  }
+
+  pub fn remove<O>(&mut self, name: ObjectName) -> &mut Self
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .actions
+      .push(CommandAction::RemoveManifest(O::kind(), name));
+    self
+  }
+
+  pub fn len(&self) -> usize {
+    self.actions.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.actions.is_empty()
+  }
+
+  /// Applies every step in order, or none of them if one fails.
+  pub async fn commit(self) -> Result<()> {
+    if let Some(e) = self.error {
+      return Err(e);
+    }
+    if self.actions.is_empty() {
+      return Ok(());
+    }
+
+    self
+      .command
+      .send_event(CommandAction::Batch(self.actions), true)
+      .await
+  }
+}
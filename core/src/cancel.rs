@@ -0,0 +1,56 @@
+use std::sync::{
+  atomic::{AtomicBool, Ordering}, Arc
+};
+
+use tokio::sync::Notify;
+
+/// CancellationToken
+///
+/// Signals a reconcile that it should stop, e.g. because its object was
+/// deleted or superseded by a newer generation. Cancellation is cooperative:
+/// long reconciles are expected to check it between steps.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+  inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+  cancelled: AtomicBool,
+  notify: Notify,
+}
+
+impl CancellationToken {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn cancel(&self) {
+    if !self.inner.cancelled.swap(true, Ordering::AcqRel) {
+      self.inner.notify.notify_waiters();
+    }
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.inner.cancelled.load(Ordering::Acquire)
+  }
+
+  /// Resolves once the token is cancelled.
+  pub async fn cancelled(&self) {
+    loop {
+      let notified = self.inner.notify.notified();
+      if self.is_cancelled() {
+        return;
+      }
+      notified.await;
+    }
+  }
+}
+
+impl std::fmt::Debug for CancellationToken {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("CancellationToken")
+      .field("cancelled", &self.is_cancelled())
+      .finish()
+  }
+}
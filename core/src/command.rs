@@ -1,22 +1,100 @@
-use std::fmt::Debug;
+use std::{any::Any, fmt::Debug, sync::Arc, time::Duration};
 
-use anyhow::{bail, Result};
 use flume::Sender;
+use tokio::sync::watch;
 
 use crate::{
-  DynObjectManifest, ObjectDefinition, ObjectKind, ObjectManifest, ObjectName
+  util::Safe, Batch, CancellationToken, Condition, Conditions, DryRun, DynObjectManifest, Error, Lister, ObjectCache, ObjectDefinition, ObjectEvent, ObjectKind, ObjectManifest, ObjectName, ObjectRef, ObjectStatus, Plan, Recorder, Result
 };
 
 /// CommandEvent
 pub struct CommandEvent {
   pub action: CommandAction,
-  pub ack: Option<catty::Sender<()>>,
+  pub ack: Option<CommandAck>,
+}
+
+/// CommandAck
+pub type CommandAck = catty::Sender<Result<CommandReply>>;
+
+/// StateReceiver
+///
+/// Latest state of an object, published after it's initialized and after
+/// each reconcile.
+pub type StateReceiver<O> =
+  watch::Receiver<Arc<<O as ObjectDefinition>::State>>;
+
+/// PatchFn
+///
+/// Modifies a manifest in place, applied by the engine to the stored one.
+pub type PatchFn =
+  Box<dyn FnOnce(&mut DynObjectManifest) -> Result<()> + Send + 'static>;
+
+/// CommandReply
+pub enum CommandReply {
+  Done,
+  Written {
+    generation: u64,
+  },
+  /// The manifest was identical to the stored one and wasn't written.
+  Unchanged {
+    generation: u64,
+  },
+  Patched {
+    generation: u64,
+    manifest: Box<DynObjectManifest>,
+  },
+  Planned(Plan),
 }
 
 /// CommandAction
 pub enum CommandAction {
-  InsertManifest(ObjectKind, Box<DynObjectManifest>, Option<ObjectName>),
+  InsertManifest(ObjectKind, Box<DynObjectManifest>, Option<ObjectRef>),
   RemoveManifest(ObjectKind, ObjectName),
+  PatchManifest(ObjectKind, ObjectName, PatchFn),
+  RecordEvent(ObjectEvent),
+  SetCondition(ObjectKind, ObjectName, Condition),
+  WatchStatus(
+    ObjectKind,
+    ObjectName,
+    catty::Sender<watch::Receiver<ObjectStatus>>,
+  ),
+  /// Subscribes to the state of an object, replying with a
+  /// [`StateReceiver`] of its kind.
+  WatchState(ObjectKind, ObjectName, catty::Sender<Box<dyn Any + Send>>),
+  /// Plans an insert or a removal without applying it.
+  DryRun(Box<CommandAction>),
+  /// Applies inserts and removals all at once, or none of them.
+  Batch(Vec<CommandAction>),
+}
+
+impl CommandAction {
+  pub fn kind(&self) -> ObjectKind {
+    match self {
+      Self::InsertManifest(kind, _, _) => kind,
+      Self::RemoveManifest(kind, _) => kind,
+      Self::PatchManifest(kind, _, _) => kind,
+      Self::RecordEvent(event) => event.kind,
+      Self::SetCondition(kind, _, _) => kind,
+      Self::WatchStatus(kind, _, _) => kind,
+      Self::WatchState(kind, _, _) => kind,
+      Self::DryRun(action) => action.kind(),
+      Self::Batch(_) => Batch::KIND,
+    }
+  }
+
+  pub fn name(&self) -> &ObjectName {
+    match self {
+      Self::InsertManifest(_, manifest, _) => manifest.name(),
+      Self::RemoveManifest(_, name) => name,
+      Self::PatchManifest(_, name, _) => name,
+      Self::RecordEvent(event) => &event.name,
+      Self::SetCondition(_, name, _) => name,
+      Self::WatchStatus(_, name, _) => name,
+      Self::WatchState(_, name, _) => name,
+      Self::DryRun(action) => action.name(),
+      Self::Batch(_) => Batch::name(),
+    }
+  }
 }
 
 impl Debug for CommandAction {
@@ -24,20 +102,121 @@ impl Debug for CommandAction {
     let variant = match self {
       Self::InsertManifest(_, _, _) => "InsertManifest",
       Self::RemoveManifest(_, _) => "RemoveManifest",
+      Self::PatchManifest(_, _, _) => "PatchManifest",
+      Self::RecordEvent(_) => "RecordEvent",
+      Self::SetCondition(_, _, _) => "SetCondition",
+      Self::WatchStatus(_, _, _) => "WatchStatus",
+      Self::WatchState(_, _, _) => "WatchState",
+      Self::DryRun(_) => "DryRun",
+      Self::Batch(_) => "Batch",
     };
 
     write!(f, "{variant}")
   }
 }
 
+/// CommandHandler
+///
+/// Handles command events in place of the engine, e.g. to record them in
+/// tests. Acknowledgements must be sent by the handler.
+pub trait CommandHandler: Safe {
+  fn handle(&self, event: CommandEvent);
+}
+
+/// CommandSender
+#[derive(Clone)]
+enum CommandSender {
+  Engine(Sender<CommandEvent>),
+  Handler(Arc<dyn CommandHandler>),
+}
+
+impl CommandSender {
+  async fn send(&self, event: CommandEvent) -> Result<()> {
+    match self {
+      Self::Engine(sender) => sender.send_async(event).await?,
+      Self::Handler(handler) => handler.handle(event),
+    }
+
+    Ok(())
+  }
+}
+
 /// Command
 pub struct Command {
-  sender: Sender<CommandEvent>,
+  sender: CommandSender,
+  cache: Option<Arc<dyn ObjectCache>>,
+  cancellation: CancellationToken,
+  object: Option<ObjectRef>,
 }
 
 impl Command {
   pub fn new(sender: Sender<CommandEvent>) -> Self {
-    Self { sender }
+    Self {
+      sender: CommandSender::Engine(sender),
+      cache: None,
+      cancellation: Default::default(),
+      object: None,
+    }
+  }
+
+  pub fn with_handler(handler: impl CommandHandler) -> Self {
+    Self {
+      sender: CommandSender::Handler(Arc::new(handler)),
+      cache: None,
+      cancellation: Default::default(),
+      object: None,
+    }
+  }
+
+  /// Serves listers from the given cache.
+  pub fn with_cache(mut self, cache: Arc<dyn ObjectCache>) -> Self {
+    self.cache = Some(cache);
+    self
+  }
+
+  /// Attaches the token of the reconcile this command is given to.
+  pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+    self.cancellation = token;
+    self
+  }
+
+  /// Attaches the object whose reconcile or termination this command is
+  /// given to.
+  pub fn with_object(mut self, object: ObjectRef) -> Self {
+    self.object = Some(object);
+    self
+  }
+
+  /// Returns the event recorder of the object being reconciled or
+  /// terminated. `None` outside of a reconcile or a termination.
+  pub fn object_recorder(&self) -> Option<Recorder> {
+    let object = self.object.clone()?;
+    Some(Recorder::for_object(object, self.clone()))
+  }
+
+  /// Returns the token cancelled when the current reconcile should stop,
+  /// because its object was deleted, superseded or ran past its deadline.
+  /// Never cancelled outside of a reconcile.
+  pub fn cancellation(&self) -> &CancellationToken {
+    &self.cancellation
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.cancellation.is_cancelled()
+  }
+
+  /// Returns a read-only view of the objects of a kind, answered from the
+  /// engine's cache.
+  pub fn lister<O>(&self) -> Result<Lister<O>>
+  where
+    O: ObjectDefinition,
+  {
+    match &self.cache {
+      Some(cache) if cache.has_kind(O::kind()) => {
+        Ok(Lister::new(cache.clone()))
+      }
+      _ => Err(Error::KindNotRegistered(O::kind())),
+    }
   }
 
   pub async fn insert_manifest<O>(
@@ -90,14 +269,17 @@ impl Command {
 
   pub async fn insert_owned_manifest<O>(
     &self,
-    owner: ObjectName,
+    owner: ObjectRef,
     manifest: ObjectManifest<O>,
   ) -> Result<()>
   where
     O: ObjectDefinition,
   {
-    if &owner == manifest.name() {
-      bail!("an object can't own itself");
+    if owner == manifest.object_ref() {
+      return Err(Error::Conflict {
+        name: owner.name,
+        reason: "an object can't own itself".into(),
+      });
     }
 
     self
@@ -114,14 +296,17 @@ impl Command {
 
   pub async fn insert_owned_manifest_async<O>(
     &self,
-    owner: ObjectName,
+    owner: ObjectRef,
     manifest: ObjectManifest<O>,
   ) -> Result<()>
   where
     O: ObjectDefinition,
   {
-    if &owner == manifest.name() {
-      bail!("an object can't own itself");
+    if owner == manifest.object_ref() {
+      return Err(Error::Conflict {
+        name: owner.name,
+        reason: "an object can't own itself".into(),
+      });
     }
 
     self
@@ -136,32 +321,404 @@ impl Command {
       .await
   }
 
-  async fn send_event(&self, action: CommandAction, ack: bool) -> Result<()> {
-    if ack {
-      let (ack_tx, ack_rx) = catty::oneshot();
-      self
-        .sender
-        .send_async(CommandEvent {
-          action,
-          ack: Some(ack_tx),
+  /// Applies a function to the props of the stored manifest, atomically with
+  /// respect to other commands, and returns the new manifest.
+  pub async fn patch<O>(
+    &self,
+    name: ObjectName,
+    patch: impl FnOnce(&mut O::Props) + Send + 'static,
+  ) -> Result<ObjectManifest<O>>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .try_patch::<O>(name, move |props| {
+        patch(props);
+        Ok(())
+      })
+      .await
+  }
+
+  /// Same as [`Command::patch`], but the patch can fail, leaving the manifest
+  /// untouched.
+  pub async fn try_patch<O>(
+    &self,
+    name: ObjectName,
+    patch: impl FnOnce(&mut O::Props) -> Result<()> + Send + 'static,
+  ) -> Result<ObjectManifest<O>>
+  where
+    O: ObjectDefinition,
+  {
+    let patch: PatchFn = Box::new(move |manifest| {
+      let manifest = manifest
+        .downcast_mut::<O>()
+        .ok_or(Error::Downcast(std::any::type_name::<O>()))?;
+      patch(&mut manifest.props)
+    });
+
+    let reply = self
+      .send_event_with_reply(CommandAction::PatchManifest(
+        O::kind(),
+        name,
+        patch,
+      ))
+      .await?;
+
+    match reply {
+      CommandReply::Patched { manifest, .. } => Ok(*manifest.as_manifest()?),
+      _ => Err(Error::UnexpectedReply("patch")),
+    }
+  }
+
+  /// Applies a JSON merge patch (RFC 7386) to the props of the stored
+  /// manifest.
+  #[cfg(feature = "serde")]
+  pub async fn merge_patch<O>(
+    &self,
+    name: ObjectName,
+    patch: serde_json::Value,
+  ) -> Result<ObjectManifest<O>>
+  where
+    O: ObjectDefinition,
+    O::Props: serde::Serialize + serde::de::DeserializeOwned,
+  {
+    let patch_name = name.clone();
+    self
+      .try_patch::<O>(name, move |props| {
+        patch_json(props, |value| {
+          json_patch::merge(value, &patch);
+          Ok(())
+        })
+        .map_err(|e| Error::invalid_patch(&patch_name, e))
+      })
+      .await
+  }
+
+  /// Applies a JSON patch (RFC 6902) to the props of the stored manifest.
+  #[cfg(feature = "serde")]
+  pub async fn json_patch<O>(
+    &self,
+    name: ObjectName,
+    patch: serde_json::Value,
+  ) -> Result<ObjectManifest<O>>
+  where
+    O: ObjectDefinition,
+    O::Props: serde::Serialize + serde::de::DeserializeOwned,
+  {
+    let patch_name = name.clone();
+    self
+      .try_patch::<O>(name, move |props| {
+        patch_json(props, |value| {
+          let patch: json_patch::Patch = serde_json::from_value(patch)?;
+          json_patch::patch(value, &patch.0)?;
+          Ok(())
         })
+        .map_err(|e| Error::invalid_patch(&patch_name, e))
+      })
+      .await
+  }
+
+  /// Plans inserts and removals without applying them.
+  pub fn dry_run(&self) -> DryRun {
+    DryRun::new(self.clone())
+  }
+
+  /// Starts a batch of inserts and removals, applied all at once.
+  pub fn batch(&self) -> Batch {
+    Batch::new(self.clone())
+  }
+
+  pub fn recorder<O>(&self, name: ObjectName) -> Recorder
+  where
+    O: ObjectDefinition,
+  {
+    Recorder::new::<O>(name, self.clone())
+  }
+
+  pub async fn record_event(&self, event: ObjectEvent) -> Result<()> {
+    self
+      .send_event(CommandAction::RecordEvent(event), true)
+      .await
+  }
+
+  pub async fn set_condition<O>(
+    &self,
+    name: ObjectName,
+    condition: Condition,
+  ) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .send_event(
+        CommandAction::SetCondition(O::kind(), name, condition),
+        true,
+      )
+      .await
+  }
+
+  pub async fn conditions<O>(&self, name: ObjectName) -> Result<Conditions>
+  where
+    O: ObjectDefinition,
+  {
+    let status = self.watch_status::<O>(name).await?;
+    let conditions = status.borrow().conditions.clone();
+
+    Ok(conditions)
+  }
+
+  pub async fn watch_status<O>(
+    &self,
+    name: ObjectName,
+  ) -> Result<watch::Receiver<ObjectStatus>>
+  where
+    O: ObjectDefinition,
+  {
+    let (watch_tx, watch_rx) = catty::oneshot();
+    self
+      .send_event(CommandAction::WatchStatus(O::kind(), name, watch_tx), true)
+      .await?;
+
+    Ok(watch_rx.await?)
+  }
+
+  /// Subscribes to the state of an object, for kinds whose controller
+  /// shares it.
+  pub async fn watch_state<O>(
+    &self,
+    name: ObjectName,
+  ) -> Result<StateReceiver<O>>
+  where
+    O: ObjectDefinition,
+  {
+    let (state_tx, state_rx) = catty::oneshot();
+    self
+      .send_event(CommandAction::WatchState(O::kind(), name, state_tx), true)
+      .await?;
+
+    state_rx
+      .await?
+      .downcast::<StateReceiver<O>>()
+      .map(|state| *state)
+      .map_err(|_| Error::Downcast(std::any::type_name::<O::State>()))
+  }
+
+  /// Reads the state of an object as of its last reconcile, without waiting
+  /// for the one in flight.
+  pub async fn inspect_state<O, R>(
+    &self,
+    name: ObjectName,
+    inspect: impl FnOnce(&O::State) -> R,
+  ) -> Result<R>
+  where
+    O: ObjectDefinition,
+  {
+    let state = self.watch_state::<O>(name).await?.borrow().clone();
+    Ok(inspect(&state))
+  }
+
+  /// Inserts a manifest and waits for its first successful reconciliation.
+  ///
+  /// Fails as soon as the kind turns out to have no controller, or the
+  /// manifest is denied admission or its object fails to initialize.
+  /// Generations the controller chooses not to reconcile count as reconciled.
+  pub async fn insert_and_wait_reconciled<O>(
+    &self,
+    manifest: ObjectManifest<O>,
+    timeout: Duration,
+  ) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    let name = manifest.name().to_owned();
+
+    with_timeout(timeout, async {
+      let reply = self
+        .send_event_with_reply(CommandAction::InsertManifest(
+          O::kind(),
+          Box::new(manifest),
+          None,
+        ))
         .await?;
-      ack_rx.await?;
+
+      let generation = match reply {
+        CommandReply::Written { generation } => generation,
+        CommandReply::Unchanged { generation } => generation,
+        _ => return Err(Error::UnexpectedReply("insert")),
+      };
+
+      let mut status = self.watch_status::<O>(name.clone()).await?;
+      let status = wait_for(&mut status, |s| {
+        s.reconciled_generation >= generation
+          || !s.managed
+          || rejected(s, generation).is_some()
+      })
+      .await
+      .map_err(|_| {
+        Error::NotFound {
+          kind: O::kind(),
+          name: name.clone(),
+        }
+      })?;
+
+      if status.reconciled_generation >= generation {
+        return Ok(());
+      }
+      if !status.managed {
+        return Err(Error::ControllerNotRegistered(O::kind()));
+      }
+
+      let condition = rejected(&status, generation).expect("checked above");
+      let source = anyhow::anyhow!(condition.message.clone());
+      match condition.reason.as_str() {
+        "AdmissionDenied" => Err(Error::admission_denied(&name, source)),
+        _ => Err(Error::controller(&name, source)),
+      }
+    })
+    .await
+  }
+
+  /// Waits until the object's conditions satisfy the given predicate.
+  pub async fn wait_for_condition<O>(
+    &self,
+    name: ObjectName,
+    predicate: impl Fn(&Conditions) -> bool,
+    timeout: Duration,
+  ) -> Result<Conditions>
+  where
+    O: ObjectDefinition,
+  {
+    with_timeout(timeout, async {
+      let mut status = self.watch_status::<O>(name.clone()).await?;
+      let status = wait_for(&mut status, |s| predicate(&s.conditions))
+        .await
+        .map_err(|_| {
+          Error::NotFound {
+            kind: O::kind(),
+            name,
+          }
+        })?;
+
+      Ok(status.conditions)
+    })
+    .await
+  }
+
+  /// Waits until the object is removed and its termination has completed.
+  pub async fn wait_for_deletion<O>(
+    &self,
+    name: ObjectName,
+    timeout: Duration,
+  ) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    with_timeout(timeout, async {
+      let mut status = match self.watch_status::<O>(name).await {
+        Ok(status) => status,
+        Err(Error::NotFound { .. }) => return Ok(()),
+        Err(e) => return Err(e),
+      };
+
+      while status.changed().await.is_ok() {}
+
+      Ok(())
+    })
+    .await
+  }
+
+  pub(crate) async fn send_event(
+    &self,
+    action: CommandAction,
+    ack: bool,
+  ) -> Result<()> {
+    if ack {
+      self.send_event_with_reply(action).await?;
     } else {
-      self
-        .sender
-        .send_async(CommandEvent { action, ack: None })
-        .await?;
+      self.sender.send(CommandEvent { action, ack: None }).await?;
     }
 
     Ok(())
   }
+
+  pub(crate) async fn send_event_with_reply(
+    &self,
+    action: CommandAction,
+  ) -> Result<CommandReply> {
+    let (ack_tx, ack_rx) = catty::oneshot();
+    self
+      .sender
+      .send(CommandEvent {
+        action,
+        ack: Some(ack_tx),
+      })
+      .await?;
+
+    ack_rx.await?
+  }
+}
+
+/// Patches props through their JSON representation.
+#[cfg(feature = "serde")]
+fn patch_json<T>(
+  props: &mut T,
+  patch: impl FnOnce(&mut serde_json::Value) -> anyhow::Result<()>,
+) -> anyhow::Result<()>
+where
+  T: serde::Serialize + serde::de::DeserializeOwned,
+{
+  let mut value = serde_json::to_value(&*props)?;
+  patch(&mut value)?;
+  *props = serde_json::from_value(value)?;
+
+  Ok(())
+}
+
+async fn with_timeout<T>(
+  timeout: Duration,
+  future: impl std::future::Future<Output = Result<T>>,
+) -> Result<T> {
+  tokio::time::timeout(timeout, future)
+    .await
+    .map_err(|_| Error::Timeout(timeout))?
+}
+
+/// Returns the condition telling why a generation of an object won't be
+/// reconciled, if the engine rejected it.
+fn rejected(status: &ObjectStatus, generation: u64) -> Option<&Condition> {
+  status
+    .conditions
+    .get(Condition::ACCEPTED)
+    .filter(|c| c.is_false() && c.observed_generation >= generation)
+}
+
+/// Resolves with the first value matching the predicate, or fails once the
+/// sender is dropped.
+async fn wait_for<T>(
+  rx: &mut watch::Receiver<T>,
+  predicate: impl Fn(&T) -> bool,
+) -> Result<T, watch::error::RecvError>
+where
+  T: Clone,
+{
+  loop {
+    {
+      let value = rx.borrow_and_update();
+      if predicate(&value) {
+        return Ok(value.clone());
+      }
+    }
+    rx.changed().await?;
+  }
 }
 
 impl Clone for Command {
   fn clone(&self) -> Self {
     Self {
       sender: self.sender.clone(),
+      cache: self.cache.clone(),
+      cancellation: self.cancellation.clone(),
+      object: self.object.clone(),
     }
   }
 }
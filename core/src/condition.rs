@@ -0,0 +1,140 @@
+use std::time::SystemTime;
+
+/// ConditionStatus
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConditionStatus {
+  True,
+  False,
+  Unknown,
+}
+
+/// Condition
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+  pub ty: String,
+  pub status: ConditionStatus,
+  pub reason: String,
+  pub message: String,
+  pub last_transition_time: SystemTime,
+  pub observed_generation: u64,
+}
+
+impl Condition {
+  pub const READY: &'static str = "Ready";
+  pub const PROGRESSING: &'static str = "Progressing";
+  pub const DEGRADED: &'static str = "Degraded";
+  /// Set by the engine once the object is admitted and initialized, or to
+  /// false with the failure as reason and message.
+  pub const ACCEPTED: &'static str = "Accepted";
+
+  pub fn new(ty: impl Into<String>, status: ConditionStatus) -> Self {
+    Self {
+      ty: ty.into(),
+      status,
+      reason: Default::default(),
+      message: Default::default(),
+      last_transition_time: SystemTime::now(),
+      observed_generation: 0,
+    }
+  }
+
+  pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+    self.reason = reason.into();
+    self
+  }
+
+  pub fn with_message(mut self, message: impl Into<String>) -> Self {
+    self.message = message.into();
+    self
+  }
+
+  pub fn with_observed_generation(mut self, generation: u64) -> Self {
+    self.observed_generation = generation;
+    self
+  }
+
+  pub fn is_true(&self) -> bool {
+    self.status == ConditionStatus::True
+  }
+
+  pub fn is_false(&self) -> bool {
+    self.status == ConditionStatus::False
+  }
+
+  pub fn is_unknown(&self) -> bool {
+    self.status == ConditionStatus::Unknown
+  }
+}
+
+/// Conditions
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Conditions(Vec<Condition>);
+
+impl Conditions {
+  /// Sets a condition, replacing any condition of the same type.
+  ///
+  /// The last transition time is only updated when the status changes.
+  /// Returns whether anything changed.
+  pub fn set(&mut self, mut condition: Condition) -> bool {
+    match self.0.iter_mut().find(|c| c.ty == condition.ty) {
+      Some(existing) => {
+        if existing.status == condition.status {
+          condition.last_transition_time = existing.last_transition_time;
+        }
+        if *existing == condition {
+          return false;
+        }
+        *existing = condition;
+      }
+      None => self.0.push(condition),
+    }
+
+    true
+  }
+
+  pub fn get(&self, ty: &str) -> Option<&Condition> {
+    self.0.iter().find(|c| c.ty == ty)
+  }
+
+  pub fn remove(&mut self, ty: &str) -> Option<Condition> {
+    let i = self.0.iter().position(|c| c.ty == ty)?;
+    Some(self.0.remove(i))
+  }
+
+  pub fn status(&self, ty: &str) -> ConditionStatus {
+    self.get(ty).map_or(ConditionStatus::Unknown, |c| c.status)
+  }
+
+  pub fn is_true(&self, ty: &str) -> bool {
+    self.status(ty) == ConditionStatus::True
+  }
+
+  pub fn is_false(&self, ty: &str) -> bool {
+    self.status(ty) == ConditionStatus::False
+  }
+
+  pub fn is_ready(&self) -> bool {
+    self.is_true(Condition::READY)
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &Condition> {
+    self.0.iter()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+}
+
+impl<'a> IntoIterator for &'a Conditions {
+  type IntoIter = std::slice::Iter<'a, Condition>;
+  type Item = &'a Condition;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.0.iter()
+  }
+}
@@ -1,8 +1,8 @@
 use std::time::Duration;
 
-use anyhow::{Error, Result};
+use anyhow::Result;
 
-use crate::{util::Safe, Command, ObjectDefinition, ObjectManifest};
+use crate::{util::Safe, Command, Error, ObjectDefinition, ObjectManifest};
 
 /// Controller
 #[allow(unused_variables)]
@@ -23,8 +23,13 @@ where
     manifest: &ObjectManifest<O>,
   ) -> Result<O::State>;
 
-  async fn terminate(&self, manifest: &ObjectManifest<O>) -> Result<()> {
-    Ok(())
+  async fn terminate(
+    &self,
+    manifest: &ObjectManifest<O>,
+    state: &mut O::State,
+    command: &Command,
+  ) -> Result<Option<Duration>> {
+    Ok(None)
   }
 
   async fn should_reconcile(
@@ -34,6 +39,9 @@ where
     Ok(true)
   }
 
+  /// Returning a delay reconciles the object again once it elapses, until
+  /// a reconcile returns `None`. Errors are passed to `reconcile_error`, then
+  /// retried with an exponential backoff from 100ms up to 5 minutes.
   async fn reconcile(
     &self,
     manifest: &ObjectManifest<O>,
@@ -44,6 +52,9 @@ where
   }
 
   async fn reconcile_error(&self, e: Error) {
+    #[cfg(feature = "tracing")]
+    tracing::error!(error = %e, "reconcile failed");
+    #[cfg(not(feature = "tracing"))]
     eprintln!("{e}")
   }
 }
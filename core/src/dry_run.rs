@@ -0,0 +1,80 @@
+use crate::{
+  Command, CommandAction, CommandReply, Error, ObjectDefinition, ObjectManifest, ObjectName, ObjectRef, Plan, Result
+};
+
+/// DryRun
+///
+/// Sends inserts and removals flagged as dry runs: the engine goes through
+/// admission, ownership checks and cascades, then replies with what would
+/// change instead of applying it.
+pub struct DryRun {
+  command: Command,
+}
+
+impl DryRun {
+  pub fn new(command: Command) -> Self {
+    Self { command }
+  }
+
+  pub async fn insert_manifest<O>(
+    &self,
+    manifest: ObjectManifest<O>,
+  ) -> Result<Plan>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .plan(CommandAction::InsertManifest(
+        O::kind(),
+        Box::new(manifest),
+        None,
+      ))
+      .await
+  }
+
+  pub async fn insert_owned_manifest<O>(
+    &self,
+    owner: ObjectRef,
+    manifest: ObjectManifest<O>,
+  ) -> Result<Plan>
+  where
+    O: ObjectDefinition,
+  {
+    if owner == manifest.object_ref() {
+      return Err(Error::Conflict {
+        name: owner.name,
+        reason: "an object can't own itself".into(),
+      });
+    }
+
+    self
+      .plan(CommandAction::InsertManifest(
+        O::kind(),
+        Box::new(manifest),
+        Some(owner),
+      ))
+      .await
+  }
+
+  /// Plans the removal of an object and of the objects it owns.
+  pub async fn remove_manifest<O>(&self, name: ObjectName) -> Result<Plan>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .plan(CommandAction::RemoveManifest(O::kind(), name))
+      .await
+  }
+
+  async fn plan(&self, action: CommandAction) -> Result<Plan> {
+    let reply = self
+      .command
+      .send_event_with_reply(CommandAction::DryRun(Box::new(action)))
+      .await?;
+
+    match reply {
+      CommandReply::Planned(plan) => Ok(plan),
+      _ => Err(Error::UnexpectedReply("dry run")),
+    }
+  }
+}
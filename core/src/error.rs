@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use crate::{ObjectKind, ObjectName};
+
+/// Result
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+  #[error("kind {0} is not registered")]
+  KindNotRegistered(ObjectKind),
+
+  #[error("kind {0} already has a controller")]
+  ControllerRegistered(ObjectKind),
+
+  #[error("kind {0} has no controller")]
+  ControllerNotRegistered(ObjectKind),
+
+  #[error("index '{index}' is not registered for kind {kind}")]
+  IndexNotRegistered { kind: ObjectKind, index: String },
+
+  #[error("no object found for name '{name}' of kind {kind}")]
+  NotFound { kind: ObjectKind, name: ObjectName },
+
+  #[error("'{name}' is already owned by '{owner}'")]
+  AlreadyOwned { owner: ObjectName, name: ObjectName },
+
+  #[error("conflict on '{name}': {reason}")]
+  Conflict { name: ObjectName, reason: String },
+
+  #[error("manifest '{name}' was denied admission: {source}")]
+  AdmissionDenied {
+    name: ObjectName,
+    #[source]
+    source: anyhow::Error,
+  },
+
+  #[error("patch of '{name}' failed: {source}")]
+  InvalidPatch {
+    name: ObjectName,
+    #[source]
+    source: anyhow::Error,
+  },
+
+  #[error("controller failed for '{name}': {source}")]
+  Controller {
+    name: ObjectName,
+    #[source]
+    source: anyhow::Error,
+  },
+
+  #[error("state of kind {0} is not shared")]
+  StateNotShared(ObjectKind),
+
+  #[error("checkpoint of '{name}' failed: {source}")]
+  Checkpoint {
+    name: ObjectName,
+    #[source]
+    source: anyhow::Error,
+  },
+
+  #[error("cannot downcast to {0}")]
+  Downcast(&'static str),
+
+  #[error("unexpected reply to {0}")]
+  UnexpectedReply(&'static str),
+
+  #[error("injected fault: {0}")]
+  InjectedFault(&'static str),
+
+  #[error("timed out after {0:?}")]
+  Timeout(Duration),
+
+  #[error("closed channel")]
+  Closed,
+}
+
+impl Error {
+  pub fn admission_denied(name: &ObjectName, source: anyhow::Error) -> Self {
+    Self::AdmissionDenied {
+      name: name.to_owned(),
+      source,
+    }
+  }
+
+  pub fn invalid_patch(name: &ObjectName, source: anyhow::Error) -> Self {
+    Self::InvalidPatch {
+      name: name.to_owned(),
+      source,
+    }
+  }
+
+  pub fn controller(name: &ObjectName, source: anyhow::Error) -> Self {
+    Self::Controller {
+      name: name.to_owned(),
+      source,
+    }
+  }
+
+  pub fn checkpoint(name: &ObjectName, source: anyhow::Error) -> Self {
+    Self::Checkpoint {
+      name: name.to_owned(),
+      source,
+    }
+  }
+}
+
+impl<T> From<flume::SendError<T>> for Error {
+  fn from(_: flume::SendError<T>) -> Self {
+    Self::Closed
+  }
+}
+
+impl From<flume::RecvError> for Error {
+  fn from(_: flume::RecvError) -> Self {
+    Self::Closed
+  }
+}
+
+impl From<catty::Disconnected> for Error {
+  fn from(_: catty::Disconnected) -> Self {
+    Self::Closed
+  }
+}
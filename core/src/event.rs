@@ -0,0 +1,114 @@
+use std::time::SystemTime;
+
+use crate::{
+  Command, ObjectDefinition, ObjectKind, ObjectName, ObjectRef, Result
+};
+
+/// EventType
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventType {
+  Normal,
+  Warning,
+}
+
+/// ObjectEvent
+#[derive(Clone, Debug)]
+pub struct ObjectEvent {
+  pub kind: ObjectKind,
+  pub name: ObjectName,
+  pub ty: EventType,
+  pub reason: String,
+  pub message: String,
+  pub count: u32,
+  pub first_timestamp: SystemTime,
+  pub last_timestamp: SystemTime,
+}
+
+impl ObjectEvent {
+  pub fn new(
+    kind: ObjectKind,
+    name: ObjectName,
+    ty: EventType,
+    reason: impl Into<String>,
+    message: impl Into<String>,
+  ) -> Self {
+    let now = SystemTime::now();
+
+    Self {
+      kind,
+      name,
+      ty,
+      reason: reason.into(),
+      message: message.into(),
+      count: 1,
+      first_timestamp: now,
+      last_timestamp: now,
+    }
+  }
+
+  pub fn is_similar(&self, other: &ObjectEvent) -> bool {
+    self.kind == other.kind
+      && self.name == other.name
+      && self.ty == other.ty
+      && self.reason == other.reason
+      && self.message == other.message
+  }
+}
+
+/// Recorder
+#[derive(Clone)]
+pub struct Recorder {
+  kind: ObjectKind,
+  name: ObjectName,
+  command: Command,
+}
+
+impl Recorder {
+  pub fn new<O>(name: ObjectName, command: Command) -> Self
+  where
+    O: ObjectDefinition,
+  {
+    Self::for_object(
+      ObjectRef {
+        kind: O::kind(),
+        name,
+      },
+      command,
+    )
+  }
+
+  pub fn for_object(object: ObjectRef, command: Command) -> Self {
+    Self {
+      kind: object.kind,
+      name: object.name,
+      command,
+    }
+  }
+
+  pub async fn normal(
+    &self,
+    reason: impl Into<String>,
+    message: impl Into<String>,
+  ) -> Result<()> {
+    self.record(EventType::Normal, reason, message).await
+  }
+
+  pub async fn warning(
+    &self,
+    reason: impl Into<String>,
+    message: impl Into<String>,
+  ) -> Result<()> {
+    self.record(EventType::Warning, reason, message).await
+  }
+
+  pub async fn record(
+    &self,
+    ty: EventType,
+    reason: impl Into<String>,
+    message: impl Into<String>,
+  ) -> Result<()> {
+    let event =
+      ObjectEvent::new(self.kind, self.name.clone(), ty, reason, message);
+    self.command.record_event(event).await
+  }
+}
@@ -1,10 +1,19 @@
 #![allow(incomplete_features)]
 #![feature(associated_type_defaults)]
-#![feature(trait_upcasting)]
 
-pub use self::{command::*, controller::*, object::*};
+pub use self::{
+  batch::*, cancel::*, command::*, condition::*, controller::*, dry_run::*, error::*, event::*, lister::*, object::*, plan::*
+};
 
+mod batch;
+mod cancel;
 mod command;
+mod condition;
 mod controller;
+mod dry_run;
+mod error;
+mod event;
+mod lister;
 mod object;
+mod plan;
 pub mod util;
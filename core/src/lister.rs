@@ -0,0 +1,90 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{
+  util::Safe, DynObjectManifest, ObjectDefinition, ObjectKind, ObjectManifest, ObjectName, Result
+};
+
+/// ObjectCache
+///
+/// Read access to the objects stored by the engine, answered synchronously.
+pub trait ObjectCache: Safe {
+  fn has_kind(&self, kind: ObjectKind) -> bool;
+
+  fn get(
+    &self,
+    kind: ObjectKind,
+    name: &ObjectName,
+  ) -> Option<Box<DynObjectManifest>>;
+
+  fn list(&self, kind: ObjectKind) -> Vec<Box<DynObjectManifest>>;
+
+  fn by_index(
+    &self,
+    kind: ObjectKind,
+    index: &str,
+    key: &str,
+  ) -> Result<Vec<Box<DynObjectManifest>>>;
+}
+
+/// Lister
+///
+/// Read-only, typed view of the objects of a kind, for lookups from within
+/// reconcile without a command round-trip.
+pub struct Lister<O>
+where
+  O: ObjectDefinition,
+{
+  cache: Arc<dyn ObjectCache>,
+  _object: PhantomData<O>,
+}
+
+impl<O> Lister<O>
+where
+  O: ObjectDefinition,
+{
+  pub fn new(cache: Arc<dyn ObjectCache>) -> Self {
+    Self {
+      cache,
+      _object: PhantomData,
+    }
+  }
+
+  pub fn get(&self, name: &ObjectName) -> Option<ObjectManifest<O>> {
+    downcast(self.cache.get(O::kind(), name)?)
+  }
+
+  pub fn list(&self) -> Vec<ObjectManifest<O>> {
+    self
+      .cache
+      .list(O::kind())
+      .into_iter()
+      .filter_map(downcast)
+      .collect()
+  }
+
+  /// Returns the objects whose index function returned the given key.
+  pub fn by_index(
+    &self,
+    index: &str,
+    key: &str,
+  ) -> Result<Vec<ObjectManifest<O>>> {
+    let manifests = self.cache.by_index(O::kind(), index, key)?;
+    Ok(manifests.into_iter().filter_map(downcast).collect())
+  }
+}
+
+impl<O> Clone for Lister<O>
+where
+  O: ObjectDefinition,
+{
+  fn clone(&self) -> Self {
+    Self::new(self.cache.clone())
+  }
+}
+
+fn downcast<O>(manifest: Box<DynObjectManifest>) -> Option<ObjectManifest<O>>
+where
+  O: ObjectDefinition,
+{
+  manifest.as_manifest().ok().map(|manifest| *manifest)
+}
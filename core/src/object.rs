@@ -1,8 +1,8 @@
 use std::{any::Any, fmt::Display, ops::Deref};
 
-use anyhow::{anyhow, Result};
-
-use crate::util::Safe;
+use crate::{
+  util::Safe, Conditions, Error, FieldChange, ObjectRef, PropsDiff, Result
+};
 
 /// ObjectKind
 pub type ObjectKind = &'static str;
@@ -15,21 +15,67 @@ pub trait ObjectDefinition: Safe {
   fn kind() -> ObjectKind {
     std::any::type_name::<Self>()
   }
+
+  /// Lists the fields that differ between two versions of props, shown by
+  /// dry runs. Defaults to a single change of the whole props, compared by
+  /// their `Debug` representation, for kinds that can't diff field by field.
+  fn diff(old: &Self::Props, new: &Self::Props) -> Option<PropsDiff> {
+    let (old, new) = (format!("{old:?}"), format!("{new:?}"));
+    let changes = match old == new {
+      true => Vec::new(),
+      false => {
+        vec![FieldChange {
+          path: String::new(),
+          old: Some(old),
+          new: Some(new),
+        }]
+      }
+    };
+
+    Some(PropsDiff::new(changes))
+  }
+
+  /// Tells whether two versions of props are identical, in which case
+  /// writing the new version is a no-op. Defaults to always writing them;
+  /// kinds with `PartialEq` props can compare them with `old == new`.
+  fn same_props(_old: &Self::Props, _new: &Self::Props) -> bool {
+    false
+  }
 }
 impl ObjectDefinition for () {}
 
 /// Props
-pub trait Props: Clone + Safe {}
-impl<T> Props for T where T: Clone + Safe {}
+pub trait Props: Clone + std::fmt::Debug + Safe {}
+impl<T> Props for T where T: Clone + std::fmt::Debug + Safe {}
 
 /// State
 pub trait State: Safe {}
 impl<T> State for T where T: Safe {}
 
+/// PersistentState
+///
+/// State that can be checkpointed and restored across restarts.
+#[cfg(feature = "serde")]
+pub trait PersistentState:
+  State + serde::Serialize + serde::de::DeserializeOwned
+{
+}
+#[cfg(feature = "serde")]
+impl<T> PersistentState for T where
+  T: State + serde::Serialize + serde::de::DeserializeOwned
+{
+}
+
 /// ObjectName
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ObjectName(String);
 
+impl ObjectName {
+  pub const fn empty() -> Self {
+    Self(String::new())
+  }
+}
+
 impl Display for ObjectName {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     self.0.fmt(f)
@@ -57,9 +103,23 @@ impl From<&str> for ObjectName {
 }
 
 /// ObjectMeta
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ObjectMeta {
   pub name: ObjectName,
+  /// Incremented by the store every time the manifest is written.
+  pub generation: u64,
+}
+
+/// ObjectStatus
+#[derive(Clone, Debug, Default)]
+pub struct ObjectStatus {
+  /// Generation of the latest manifest written to the store.
+  pub generation: u64,
+  /// Generation of the latest manifest successfully reconciled.
+  pub reconciled_generation: u64,
+  /// Whether a controller handles the object.
+  pub managed: bool,
+  pub conditions: Conditions,
 }
 
 /// ObjectManifest
@@ -78,6 +138,10 @@ where
   pub fn name(&self) -> &ObjectName {
     &self.meta.name
   }
+
+  pub fn object_ref(&self) -> ObjectRef {
+    ObjectRef::new::<O>(self.name().to_owned())
+  }
 }
 
 impl<O> Clone for ObjectManifest<O>
@@ -95,6 +159,7 @@ where
 /// AnyObjectManifest
 pub trait AnyObjectManifest: Any + Safe {
   fn name(&self) -> &ObjectName;
+  fn clone_manifest(&self) -> Box<DynObjectManifest>;
 }
 
 impl<O> AnyObjectManifest for ObjectManifest<O>
@@ -104,17 +169,35 @@ where
   fn name(&self) -> &ObjectName {
     ObjectManifest::name(self)
   }
+
+  fn clone_manifest(&self) -> Box<DynObjectManifest> {
+    Box::new(self.clone())
+  }
 }
 
 pub type DynObjectManifest = dyn AnyObjectManifest + Send + Sync + 'static;
 
 impl DynObjectManifest {
+  pub fn downcast_ref<O>(&self) -> Option<&ObjectManifest<O>>
+  where
+    O: ObjectDefinition,
+  {
+    (self as &dyn Any).downcast_ref()
+  }
+
+  pub fn downcast_mut<O>(&mut self) -> Option<&mut ObjectManifest<O>>
+  where
+    O: ObjectDefinition,
+  {
+    (self as &mut dyn Any).downcast_mut()
+  }
+
   pub fn as_manifest<O>(self: Box<Self>) -> Result<Box<ObjectManifest<O>>>
   where
     O: ObjectDefinition,
   {
     (self as Box<dyn Any + Send + Sync>)
       .downcast()
-      .map_err(|_| anyhow!("cannot downcast to {}", std::any::type_name::<O>()))
+      .map_err(|_| Error::Downcast(std::any::type_name::<O>()))
   }
 }
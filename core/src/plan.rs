@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+
+use crate::{ObjectDefinition, ObjectKind, ObjectName};
+
+/// ObjectRef
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ObjectRef {
+  pub kind: ObjectKind,
+  pub name: ObjectName,
+}
+
+impl ObjectRef {
+  pub fn new<O>(name: ObjectName) -> Self
+  where
+    O: ObjectDefinition,
+  {
+    Self {
+      kind: O::kind(),
+      name,
+    }
+  }
+}
+
+/// PlannedChange
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlannedChange {
+  Create,
+  Update,
+  Unchanged,
+  Remove,
+  /// Removing an object that doesn't exist.
+  Absent,
+}
+
+/// FieldChange
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldChange {
+  /// JSON pointer of the field, empty for the props themselves.
+  pub path: String,
+  pub old: Option<String>,
+  pub new: Option<String>,
+}
+
+/// PropsDiff
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PropsDiff(Vec<FieldChange>);
+
+impl PropsDiff {
+  pub fn new(changes: Vec<FieldChange>) -> Self {
+    Self(changes)
+  }
+
+  /// Diffs the JSON representations of two versions of props, field by
+  /// field.
+  #[cfg(feature = "serde")]
+  pub fn json<T>(old: &T, new: &T) -> serde_json::Result<Self>
+  where
+    T: serde::Serialize,
+  {
+    let (mut old_fields, mut new_fields) = (BTreeMap::new(), BTreeMap::new());
+    flatten(String::new(), &serde_json::to_value(old)?, &mut old_fields);
+    flatten(String::new(), &serde_json::to_value(new)?, &mut new_fields);
+
+    Ok(Self::from_fields(old_fields, new_fields))
+  }
+
+  /// Diffs two flattened versions of props, keyed by field path.
+  pub fn from_fields(
+    mut old: BTreeMap<String, String>,
+    new: BTreeMap<String, String>,
+  ) -> Self {
+    let mut changes = Vec::new();
+
+    for (path, new) in new {
+      match old.remove(&path) {
+        Some(old) if old == new => {}
+        old => {
+          changes.push(FieldChange {
+            path,
+            old,
+            new: Some(new),
+          })
+        }
+      }
+    }
+    for (path, old) in old {
+      changes.push(FieldChange {
+        path,
+        old: Some(old),
+        new: None,
+      });
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Self(changes)
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &FieldChange> {
+    self.0.iter()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+}
+
+impl<'a> IntoIterator for &'a PropsDiff {
+  type IntoIter = std::slice::Iter<'a, FieldChange>;
+  type Item = &'a FieldChange;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.0.iter()
+  }
+}
+
+/// Plan
+///
+/// Outcome of a dry run: what a command would change if it was applied.
+#[derive(Clone, Debug)]
+pub struct Plan {
+  pub kind: ObjectKind,
+  pub name: ObjectName,
+  pub change: PlannedChange,
+  /// Props changes of an update, unless the kind opted out of diffing.
+  pub diff: Option<PropsDiff>,
+  /// Owned objects a removal would delete along with the object.
+  pub cascade: Vec<ObjectRef>,
+}
+
+#[cfg(feature = "serde")]
+fn flatten(
+  path: String,
+  value: &serde_json::Value,
+  fields: &mut BTreeMap<String, String>,
+) {
+  use serde_json::Value;
+
+  match value {
+    Value::Object(map) if !map.is_empty() => {
+      for (key, value) in map {
+        let key = key.replace('~', "~0").replace('/', "~1");
+        flatten(format!("{path}/{key}"), value, fields);
+      }
+    }
+    Value::Array(items) if !items.is_empty() => {
+      for (i, value) in items.iter().enumerate() {
+        flatten(format!("{path}/{i}"), value, fields);
+      }
+    }
+    _ => {
+      fields.insert(path, value.to_string());
+    }
+  }
+}
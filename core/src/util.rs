@@ -1,3 +1,31 @@
+use std::panic::AssertUnwindSafe;
+
+use crate::{DynObjectManifest, Error, ObjectName, PatchFn, Result};
+
 /// Safe
 pub trait Safe: Send + Sync + 'static {}
 impl<T: Send + Sync + 'static> Safe for T {}
+
+/// Applies a patch to a manifest, turning a panic of the patch into an
+/// error instead of unwinding through the caller.
+pub fn apply_patch(
+  name: &ObjectName,
+  manifest: &mut DynObjectManifest,
+  patch: PatchFn,
+) -> Result<()> {
+  std::panic::catch_unwind(AssertUnwindSafe(|| patch(manifest))).unwrap_or_else(
+    |payload| {
+      let message = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => {
+          payload
+            .downcast_ref::<&str>()
+            .map_or("patch panicked", |message| message)
+            .to_owned()
+        }
+      };
+
+      Err(Error::invalid_patch(name, anyhow::anyhow!(message)))
+    },
+  )
+}
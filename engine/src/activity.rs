@@ -0,0 +1,45 @@
+use std::sync::{
+  atomic::{AtomicUsize, Ordering}, Arc
+};
+
+use crate::Metrics;
+
+/// Activity
+///
+/// Tracks work in progress across the engine, operators and reconcilers so
+/// callers can tell when the engine has settled.
+#[derive(Clone)]
+pub struct Activity {
+  busy: Arc<AtomicUsize>,
+  metrics: Metrics,
+}
+
+impl Activity {
+  pub fn new(metrics: Metrics) -> Self {
+    Self {
+      busy: Default::default(),
+      metrics,
+    }
+  }
+
+  /// Returns whether no command, event or reconcile is pending.
+  ///
+  /// Scheduled requeues and retries don't count as pending work.
+  pub fn is_idle(&self) -> bool {
+    self.busy.load(Ordering::Acquire) == 0 && self.metrics.queued() == 0
+  }
+
+  pub(crate) fn enter(&self) -> ActivityGuard {
+    self.busy.fetch_add(1, Ordering::AcqRel);
+    ActivityGuard(self.busy.clone())
+  }
+}
+
+/// ActivityGuard
+pub(crate) struct ActivityGuard(Arc<AtomicUsize>);
+
+impl Drop for ActivityGuard {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::AcqRel);
+  }
+}
@@ -0,0 +1,60 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use gusto_core::{
+  DynObjectManifest, Error, ObjectCache, ObjectKind, ObjectName, Result
+};
+use parking_lot::RwLock;
+
+use crate::DynStore;
+
+/// Cache
+///
+/// Serves listers straight from the stores, without going through the engine
+/// loop.
+#[derive(Clone, Default)]
+pub(crate) struct Cache {
+  stores: Arc<RwLock<BTreeMap<ObjectKind, Arc<DynStore>>>>,
+}
+
+impl Cache {
+  pub(crate) fn register_store(&self, kind: ObjectKind, store: Arc<DynStore>) {
+    self.stores.write().entry(kind).or_insert(store);
+  }
+
+  fn store(&self, kind: ObjectKind) -> Option<Arc<DynStore>> {
+    self.stores.read().get(kind).cloned()
+  }
+}
+
+impl ObjectCache for Cache {
+  fn has_kind(&self, kind: ObjectKind) -> bool {
+    self.stores.read().contains_key(kind)
+  }
+
+  fn get(
+    &self,
+    kind: ObjectKind,
+    name: &ObjectName,
+  ) -> Option<Box<DynObjectManifest>> {
+    self.store(kind)?.get(name)
+  }
+
+  fn list(&self, kind: ObjectKind) -> Vec<Box<DynObjectManifest>> {
+    self
+      .store(kind)
+      .map(|store| store.list())
+      .unwrap_or_default()
+  }
+
+  fn by_index(
+    &self,
+    kind: ObjectKind,
+    index: &str,
+    key: &str,
+  ) -> Result<Vec<Box<DynObjectManifest>>> {
+    self
+      .store(kind)
+      .ok_or(Error::KindNotRegistered(kind))?
+      .by_index(index, key)
+  }
+}
@@ -0,0 +1,169 @@
+use std::{
+  collections::BTreeMap, fs::{self, File}, io::{ErrorKind, Write}, path::{Path, PathBuf}
+};
+
+use gusto_core::{util::Safe, Error, ObjectKind, ObjectName, Result};
+use parking_lot::RwLock;
+use serde_json::Value;
+
+/// Checkpoints
+///
+/// Where object states are saved after each successful reconcile, and
+/// restored from when objects are created again.
+#[async_trait::async_trait]
+pub trait Checkpoints: Safe {
+  async fn load(
+    &self,
+    kind: ObjectKind,
+    name: &ObjectName,
+  ) -> Result<Option<Value>>;
+  async fn save(
+    &self,
+    kind: ObjectKind,
+    name: &ObjectName,
+    state: Value,
+  ) -> Result<()>;
+  async fn remove(&self, kind: ObjectKind, name: &ObjectName) -> Result<()>;
+}
+
+/// MemoryCheckpoints
+///
+/// Keeps checkpoints in memory, which only survives operator restarts.
+#[derive(Default)]
+pub struct MemoryCheckpoints {
+  states: RwLock<BTreeMap<(ObjectKind, ObjectName), Value>>,
+}
+
+#[async_trait::async_trait]
+impl Checkpoints for MemoryCheckpoints {
+  async fn load(
+    &self,
+    kind: ObjectKind,
+    name: &ObjectName,
+  ) -> Result<Option<Value>> {
+    Ok(self.states.read().get(&(kind, name.to_owned())).cloned())
+  }
+
+  async fn save(
+    &self,
+    kind: ObjectKind,
+    name: &ObjectName,
+    state: Value,
+  ) -> Result<()> {
+    self.states.write().insert((kind, name.to_owned()), state);
+    Ok(())
+  }
+
+  async fn remove(&self, kind: ObjectKind, name: &ObjectName) -> Result<()> {
+    self.states.write().remove(&(kind, name.to_owned()));
+    Ok(())
+  }
+}
+
+/// FileCheckpoints
+///
+/// Keeps checkpoints as JSON files, one directory per kind. Files are read
+/// and written on the blocking thread pool.
+pub struct FileCheckpoints {
+  dir: PathBuf,
+}
+
+impl FileCheckpoints {
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    Self { dir: dir.into() }
+  }
+
+  fn path(&self, kind: ObjectKind, name: &ObjectName) -> PathBuf {
+    self
+      .dir
+      .join(escape(kind))
+      .join(format!("{}.json", escape(name)))
+  }
+}
+
+#[async_trait::async_trait]
+impl Checkpoints for FileCheckpoints {
+  async fn load(
+    &self,
+    kind: ObjectKind,
+    name: &ObjectName,
+  ) -> Result<Option<Value>> {
+    let path = self.path(kind, name);
+    let data = match blocking(name, move || fs::read(path)).await {
+      Ok(data) => data,
+      Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+      Err(e) => return Err(Error::checkpoint(name, e.into())),
+    };
+
+    serde_json::from_slice(&data)
+      .map(Some)
+      .map_err(|e| Error::checkpoint(name, e.into()))
+  }
+
+  async fn save(
+    &self,
+    kind: ObjectKind,
+    name: &ObjectName,
+    state: Value,
+  ) -> Result<()> {
+    let path = self.path(kind, name);
+    let data = serde_json::to_vec(&state)
+      .map_err(|e| Error::checkpoint(name, e.into()))?;
+
+    blocking(name, move || write_durably(&path, &data))
+      .await
+      .map_err(|e| Error::checkpoint(name, e.into()))
+  }
+
+  async fn remove(&self, kind: ObjectKind, name: &ObjectName) -> Result<()> {
+    let path = self.path(kind, name);
+    match blocking(name, move || fs::remove_file(path)).await {
+      Err(e) if e.kind() != ErrorKind::NotFound => {
+        Err(Error::checkpoint(name, e.into()))
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+/// Runs file operations on the blocking thread pool.
+async fn blocking<T, F>(name: &ObjectName, f: F) -> std::io::Result<T>
+where
+  F: FnOnce() -> std::io::Result<T> + Send + 'static,
+  T: Send + 'static,
+{
+  tokio::task::spawn_blocking(f).await.unwrap_or_else(|e| {
+    Err(std::io::Error::other(format!(
+      "checkpoint of '{name}' panicked: {e}"
+    )))
+  })
+}
+
+/// Writes then renames a temporary file, syncing both the file and its
+/// directory, so a crash never leaves a truncated or lost checkpoint.
+fn write_durably(path: &Path, data: &[u8]) -> std::io::Result<()> {
+  let dir = path.parent().expect("checkpoint paths have a directory");
+  fs::create_dir_all(dir)?;
+
+  let tmp = path.with_extension("json.tmp");
+  let mut file = File::create(&tmp)?;
+  file.write_all(data)?;
+  file.sync_all()?;
+  fs::rename(&tmp, path)?;
+
+  File::open(dir)?.sync_all()
+}
+
+/// Escapes a kind or name into a file name.
+fn escape(s: &str) -> String {
+  s.bytes()
+    .map(|b| {
+      match b {
+        b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => {
+          (b as char).to_string()
+        }
+        _ => format!("%{b:02X}"),
+      }
+    })
+    .collect()
+}
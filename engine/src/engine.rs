@@ -1,25 +1,58 @@
 use std::{
-  collections::{BTreeMap, VecDeque}, sync::Arc
+  collections::{btree_map::Entry, BTreeMap, VecDeque}, future::Future, pin::Pin, sync::Arc
 };
 
-use anyhow::{anyhow, Result};
 use flume::{Receiver, Sender};
 use gusto_core::{
-  Command, CommandAction, CommandEvent, Controller, ObjectDefinition, ObjectKind
+  Command, CommandAck, CommandAction, CommandEvent, CommandReply, Controller, DynObjectManifest, Error, ObjectDefinition, ObjectKind, ObjectName, ObjectRef, Plan, PlannedChange, Result
 };
-use tokio::task::JoinHandle;
+use parking_lot::RwLock;
+use tokio::{sync::watch, task::JoinHandle};
 
-use crate::{DynStore, Operator, Owners, Store};
+use crate::{
+  cache::Cache, log::{self, Instrument}, operator::{Admission, StopSender}, state::AnyStates, Activity, ControllerOptions, DynStore, EngineHandle, Events, Faults, Inspector, Limiter, Metrics, ObjectOptions, Operator, OperatorContext, Owners, RateLimit, Snapshot, Store, WatcherId
+};
 
 type StartOperatorFn = Box<dyn FnOnce() -> JoinHandle<()> + Send>;
+type PlanFuture = Pin<Box<dyn Future<Output = Result<Plan>> + Send>>;
+pub(crate) type ControlFn = Box<dyn FnOnce(&mut Engine) + Send>;
+
+/// Staged
+///
+/// Step of a batch, checked and ready to be written.
+enum Staged {
+  Insert(
+    ObjectKind,
+    Arc<DynStore>,
+    Box<DynObjectManifest>,
+    Option<ObjectRef>,
+  ),
+  Remove(ObjectKind, Arc<DynStore>, ObjectName),
+}
 
 /// Engine
 pub struct Engine {
   stores: BTreeMap<ObjectKind, Arc<DynStore>>,
-  owners: Owners,
+  owners: Arc<RwLock<Owners>>,
+  admissions: BTreeMap<ObjectKind, Arc<dyn Admission>>,
+  states: BTreeMap<ObjectKind, Arc<dyn AnyStates>>,
+  operators: BTreeMap<ObjectKind, StopSender>,
+  draining: BTreeMap<ObjectKind, watch::Receiver<bool>>,
+  /// Store watchers of each controller, by the kind of the watched store.
+  watchers: BTreeMap<ObjectKind, Vec<(ObjectKind, WatcherId)>>,
   start_queue: VecDeque<StartOperatorFn>,
+  started: bool,
   command_tx: Sender<CommandEvent>,
   command_rx: Receiver<CommandEvent>,
+  control_tx: Sender<ControlFn>,
+  control_rx: Receiver<ControlFn>,
+  metrics: Metrics,
+  events: Events,
+  activity: Activity,
+  faults: Faults,
+  inspector: Inspector,
+  cache: Cache,
+  limiter: Limiter,
 }
 
 impl Engine {
@@ -27,11 +60,41 @@ impl Engine {
   where
     O: ObjectDefinition,
   {
-    // let store = Arc::new(Store::<O>::default());
-    self
-      .stores
-      .entry(O::kind())
-      .or_insert_with(|| Arc::new(Store::<O>::default()));
+    self.register_object_with::<O>(ObjectOptions::default())
+  }
+
+  /// Registers a kind along with its indexes. Registering a kind again adds
+  /// the new indexes to its store.
+  pub fn register_object_with<O>(&mut self, options: ObjectOptions<O>)
+  where
+    O: ObjectDefinition,
+  {
+    let store = self.stores.entry(O::kind()).or_insert_with(|| {
+      let store = Arc::new(Store::<O>::default());
+
+      let depth_store = store.clone();
+      self.metrics.register_depth(
+        vec![
+          ("channel", "store".to_owned()),
+          ("kind", O::kind().to_owned()),
+        ],
+        move || depth_store.pending_events(),
+      );
+
+      self.inspector.register_store(O::kind(), store.clone());
+      self.cache.register_store(O::kind(), store.clone());
+
+      // Events outlive removals until terminate is done with the object.
+      let events = self.events.clone();
+      store.on_release(move |name| events.forget(O::kind(), name));
+
+      store
+    });
+
+    let store = store.clone().as_store::<O>().expect("store kind matches");
+    for (name, func) in options.indexes {
+      store.add_index(name, func);
+    }
   }
 
   pub fn register_controller<O>(
@@ -41,61 +104,231 @@ impl Engine {
   where
     O: ObjectDefinition,
   {
-    let store = self.get_store::<O>()?;
-    let command = self.command();
+    self.register_controller_with(controller, ControllerOptions::default())
+  }
 
-    let mut op = Operator::new(controller, command, store);
-    let start_op = Box::new(|| tokio::spawn(async move { op.start().await }));
+  /// Registers the controller of a kind. Once the engine is started, the
+  /// controller takes over the objects already stored.
+  pub fn register_controller_with<O>(
+    &mut self,
+    controller: impl Controller<O>,
+    options: ControllerOptions<O>,
+  ) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    if self.operators.contains_key(O::kind()) {
+      return Err(Error::ControllerRegistered(O::kind()));
+    }
 
-    self.start_queue.push_back(start_op);
+    let op = self.build_operator(controller, options)?;
+    self.start_operator(op);
 
     Ok(())
   }
 
-  pub fn command(&self) -> Command {
-    Command::new(self.command_tx.clone())
+  /// Replaces the controller of a kind on the running engine. The new
+  /// controller takes over the objects once the old one is drained, which
+  /// the returned future waits for.
+  pub(crate) fn replace_controller_with<O>(
+    &mut self,
+    controller: impl Controller<O>,
+    options: ControllerOptions<O>,
+  ) -> Result<impl Future<Output = ()> + Send + 'static>
+  where
+    O: ObjectDefinition,
+  {
+    let stop_tx = self
+      .operators
+      .get(O::kind())
+      .cloned()
+      .ok_or(Error::ControllerNotRegistered(O::kind()))?;
+
+    let op = self.build_operator(controller, options)?;
+    let drained_rx = self.stop_operator(O::kind(), stop_tx);
+    self.start_operator(op);
+
+    Ok(drained(drained_rx))
   }
 
-  pub async fn start(mut self) {
-    while let Some(start_fn) = self.start_queue.pop_front() {
-      (start_fn)();
-    }
+  /// Unregisters the controller of a kind from the running engine. Objects
+  /// stay in the store, unmanaged, once the controller is drained, which the
+  /// returned future waits for.
+  pub(crate) fn unregister_controller<O>(
+    &mut self,
+  ) -> Result<impl Future<Output = ()> + Send + 'static>
+  where
+    O: ObjectDefinition,
+  {
+    let stop_tx = self
+      .operators
+      .remove(O::kind())
+      .ok_or(Error::ControllerNotRegistered(O::kind()))?;
+
+    self.admissions.remove(O::kind());
+    self.states.remove(O::kind());
+    self.unwatch(O::kind());
+    self.inspector.unregister_operator(O::kind());
+    self.store::<O>()?.set_managed(false);
+    let drained_rx = self.stop_operator(O::kind(), stop_tx);
+
+    Ok(drained(drained_rx))
+  }
+
+  /// Asks an operator to stop, tracking its drain so the next operator of
+  /// the kind waits for it, until it's drained.
+  fn stop_operator(
+    &mut self,
+    kind: ObjectKind,
+    stop_tx: StopSender,
+  ) -> watch::Receiver<bool> {
+    let (drained_tx, drained_rx) = watch::channel(false);
+    stop_tx.send(drained_tx).ok();
+    self.draining.insert(kind, drained_rx.clone());
+
+    let control_tx = self.control_tx.clone();
+    let draining = drained_rx.clone();
+    tokio::spawn(async move {
+      drained(draining.clone()).await;
+      let forget: ControlFn = Box::new(move |engine| {
+        if let Entry::Occupied(entry) = engine.draining.entry(kind) {
+          if entry.get().same_channel(&draining) {
+            entry.remove();
+          }
+        }
+      });
+      control_tx.send(forget).ok();
+    });
 
-    while let Ok(event) = self.command_rx.recv_async().await {
-      println!("received command event: {:?}", event.action);
+    drained_rx
+  }
 
-      if let Err(e) = self.handle_event(event).await {
-        eprintln!("{e}");
+  /// Drops the store watchers of a kind's controller.
+  fn unwatch(&mut self, kind: ObjectKind) {
+    for (watched, id) in self.watchers.remove(kind).unwrap_or_default() {
+      if let Some(store) = self.stores.get(watched) {
+        store.unwatch(id);
       }
     }
   }
 
-  async fn handle_event(&mut self, event: CommandEvent) -> Result<()> {
-    match event.action {
-      CommandAction::InsertManifest(kind, manifest, owner) => {
-        if let Some(owner) = owner {
-          self.owners.own(owner, kind, manifest.name().to_owned())?;
+  /// Starts an operator along with the engine, or right away if the engine
+  /// is running, once the previous operator of its kind is drained.
+  fn start_operator<C, O>(&mut self, mut op: Operator<C, O>)
+  where
+    C: Controller<O>,
+    O: ObjectDefinition,
+  {
+    let previous = self.draining.get(O::kind()).cloned();
+    let activity = self.activity.clone();
+    let start_op: StartOperatorFn = Box::new(|| {
+      tokio::spawn(async move {
+        if let Some(previous) = previous {
+          let _activity = activity.enter();
+          drained(previous).await;
         }
-        self.get_store_kind(kind)?.insert(manifest)?;
+        op.start().await
+      })
+    });
+
+    match self.started {
+      true => {
+        start_op();
       }
-      CommandAction::RemoveManifest(kind, name) => {
-        if let Some(ownerships) = self.owners.remove_owner(&name) {
-          for owned in ownerships {
-            self.get_store_kind(owned.kind)?.remove(&owned.name)?;
+      false => self.start_queue.push_back(start_op),
+    }
+  }
+
+  /// Builds the operator of a controller and routes the kind to it.
+  fn build_operator<C, O>(
+    &mut self,
+    controller: C,
+    options: ControllerOptions<O>,
+  ) -> Result<Operator<C, O>>
+  where
+    C: Controller<O>,
+    O: ObjectDefinition,
+  {
+    let store = self.store::<O>()?;
+
+    let op = Operator::new(
+      controller,
+      store,
+      self.operator_context(),
+      options.reconcile,
+      options.share_state,
+      options.persist_state,
+    );
+    let mut watchers = Vec::new();
+    for watch in options.watches {
+      match watch(self, op.trigger()) {
+        Ok(watcher) => watchers.push(watcher),
+        Err(e) => {
+          for (watched, id) in watchers {
+            self.stores[watched].unwatch(id);
           }
+          return Err(e);
         }
-        self.get_store_kind(kind)?.remove(&name)?;
       }
     }
+    self.unwatch(O::kind());
+    self.watchers.insert(O::kind(), watchers);
+    self.inspector.register_operator(O::kind(), op.inspector());
+    self.admissions.insert(O::kind(), op.admission());
+    match op.states() {
+      Some(states) => self.states.insert(O::kind(), states),
+      None => self.states.remove(O::kind()),
+    };
+    self.operators.insert(O::kind(), op.stopper());
 
-    if let Some(ack) = event.ack {
-      ack.send(()).ok();
-    }
+    Ok(op)
+  }
 
-    Ok(())
+  /// Limits the rate of reconciles across all controllers.
+  pub fn set_rate_limit(&self, limit: RateLimit) {
+    self.limiter.set(limit);
   }
 
-  fn get_store<O>(&self) -> Result<Arc<Store<O>>>
+  /// Returns a handle to register kinds and controllers once started.
+  pub fn handle(&self) -> EngineHandle {
+    EngineHandle::new(self.control_tx.clone())
+  }
+
+  pub fn command(&self) -> Command {
+    Command::new(self.command_tx.clone())
+      .with_cache(Arc::new(self.cache.clone()))
+  }
+
+  pub fn metrics(&self) -> Metrics {
+    self.metrics.clone()
+  }
+
+  pub fn events(&self) -> Events {
+    self.events.clone()
+  }
+
+  pub fn activity(&self) -> Activity {
+    self.activity.clone()
+  }
+
+  pub fn faults(&self) -> Faults {
+    self.faults.clone()
+  }
+
+  pub fn inspector(&self) -> Inspector {
+    self.inspector.clone()
+  }
+
+  /// Captures every registered kind, object and ownership.
+  pub fn snapshot(&self) -> Snapshot {
+    self.inspector.snapshot()
+  }
+
+  pub(crate) fn owners(&self) -> Arc<RwLock<Owners>> {
+    self.owners.clone()
+  }
+
+  pub fn store<O>(&self) -> Result<Arc<Store<O>>>
   where
     O: ObjectDefinition,
   {
@@ -103,29 +336,396 @@ impl Engine {
       .stores
       .get(O::kind())
       .cloned()
-      .ok_or_else(|| anyhow!("no store found for kind {}", O::kind()))
+      .ok_or(Error::KindNotRegistered(O::kind()))
       .and_then(|s| s.as_store())
   }
 
+  pub async fn start(mut self) {
+    while let Some(start_fn) = self.start_queue.pop_front() {
+      (start_fn)();
+    }
+    self.started = true;
+
+    let command_rx = self.command_rx.clone();
+    let control_rx = self.control_rx.clone();
+    loop {
+      tokio::select! {
+        event = command_rx.recv_async() => match event {
+          Ok(event) => self.handle_command(event).await,
+          Err(_) => break,
+        },
+        Ok(control) = control_rx.recv_async() => {
+          let _activity = self.activity.enter();
+          control(&mut self);
+        }
+      }
+    }
+  }
+
+  async fn handle_command(&mut self, event: CommandEvent) {
+    let _activity = self.activity.enter();
+
+    let action = format!("{:?}", event.action);
+    let kind = event.action.kind();
+    let span = log::span!(
+      "command",
+      action = ?event.action,
+      kind = event.action.kind(),
+      name = %event.action.name(),
+    );
+
+    let res = match event.action {
+      CommandAction::DryRun(dry_run) => {
+        match self.plan(*dry_run) {
+          Ok(plan) => {
+            // Admission may be slow, or send commands of its own.
+            let activity = self.activity.enter();
+            let metrics = self.metrics.clone();
+            tokio::spawn(
+              async move {
+                let _activity = activity;
+                let res = plan.await.map(CommandReply::Planned);
+                metrics.command(&action, kind, res.is_ok());
+                reply(event.ack, res);
+              }
+              .instrument(span),
+            );
+            return;
+          }
+          Err(e) => Err(e),
+        }
+      }
+      action => {
+        async {
+          log::debug!("received command event");
+          self.handle_action(action)
+        }
+        .instrument(span)
+        .await
+      }
+    };
+
+    self.metrics.command(&action, kind, res.is_ok());
+    reply(event.ack, res);
+  }
+
+  fn handle_action(&mut self, action: CommandAction) -> Result<CommandReply> {
+    match action {
+      CommandAction::DryRun(_) => {
+        unreachable!("dry runs are planned by handle_command")
+      }
+      CommandAction::InsertManifest(..)
+      | CommandAction::RemoveManifest(..)
+      | CommandAction::PatchManifest(..)
+        if self.faults.fail_write() =>
+      {
+        return Err(Error::InjectedFault("write"));
+      }
+      CommandAction::InsertManifest(kind, manifest, owner) => {
+        return self.insert(kind, manifest, owner);
+      }
+      CommandAction::RemoveManifest(kind, name) => self.remove(kind, &name)?,
+      CommandAction::Batch(actions) => {
+        if self.faults.fail_write() {
+          return Err(Error::InjectedFault("write"));
+        }
+
+        for step in self.stage_batch(actions)? {
+          match step {
+            Staged::Insert(kind, store, manifest, owner) => {
+              if let Some(owner) = owner {
+                self.owners.write().own(
+                  owner,
+                  kind,
+                  manifest.name().to_owned(),
+                )?;
+              }
+              if !store.insert(manifest)?.changed {
+                self.metrics.unchanged_write(kind);
+              }
+            }
+            Staged::Remove(kind, store, name) => {
+              store.remove(&name)?;
+              self.owners.write().release(kind, &name);
+            }
+          }
+        }
+      }
+      CommandAction::PatchManifest(kind, name, patch) => {
+        let (written, manifest) =
+          self.get_store_kind(kind)?.modify(&name, patch)?;
+        if !written.changed {
+          self.metrics.unchanged_write(kind);
+        }
+        return Ok(CommandReply::Patched {
+          generation: written.generation,
+          manifest,
+        });
+      }
+      CommandAction::RecordEvent(event) => {
+        // Released objects have no status left, nor events to add to.
+        self.get_store_kind(event.kind)?.watch_status(&event.name)?;
+        self.events.record(event);
+      }
+      CommandAction::SetCondition(kind, name, condition) => {
+        self.get_store_kind(kind)?.set_condition(&name, condition)?;
+      }
+      CommandAction::WatchStatus(kind, name, watch_tx) => {
+        let watch_rx = self.get_store_kind(kind)?.watch_status(&name)?;
+        watch_tx.send(watch_rx).ok();
+      }
+      CommandAction::WatchState(kind, name, state_tx) => {
+        let states =
+          self.states.get(kind).ok_or(Error::StateNotShared(kind))?;
+        state_tx.send(states.watch(&name)?).ok();
+      }
+    }
+
+    Ok(CommandReply::Done)
+  }
+
+  fn insert(
+    &mut self,
+    kind: ObjectKind,
+    manifest: Box<DynObjectManifest>,
+    owner: Option<ObjectRef>,
+  ) -> Result<CommandReply> {
+    if let Some(owner) = owner {
+      self
+        .owners
+        .write()
+        .own(owner, kind, manifest.name().to_owned())?;
+    }
+
+    let written = self.get_store_kind(kind)?.insert(manifest)?;
+    if !written.changed {
+      self.metrics.unchanged_write(kind);
+      return Ok(CommandReply::Unchanged {
+        generation: written.generation,
+      });
+    }
+
+    Ok(CommandReply::Written {
+      generation: written.generation,
+    })
+  }
+
+  fn remove(&mut self, kind: ObjectKind, name: &ObjectName) -> Result<()> {
+    let owner = ObjectRef {
+      kind,
+      name: name.to_owned(),
+    };
+    // Ownerships are released once removed, so that removal events still
+    // reach the owners of what's removed.
+    let ownerships = self.owners.read().owned(&owner).cloned();
+    for owned in ownerships.into_iter().flatten() {
+      self.get_store_kind(owned.kind)?.remove(&owned.name)?;
+      self.owners.write().release(owned.kind, &owned.name);
+    }
+    self.get_store_kind(kind)?.remove(name)?;
+    self.owners.write().release(kind, name);
+
+    Ok(())
+  }
+
+  /// Resolves every step of a batch into store writes, replaying ownership
+  /// changes on a copy of the owners. Nothing is applied unless every step
+  /// checks out, after which writing to the stores can't fail.
+  fn stage_batch(&self, actions: Vec<CommandAction>) -> Result<Vec<Staged>> {
+    let mut owners = self.owners.read().clone();
+    let mut steps = Vec::with_capacity(actions.len());
+
+    for action in actions {
+      match action {
+        CommandAction::InsertManifest(kind, manifest, owner) => {
+          let store = self.get_store_kind(kind)?;
+          store.check(&*manifest)?;
+          if let Some(owner) = &owner {
+            owners.own(owner.clone(), kind, manifest.name().to_owned())?;
+          }
+          steps.push(Staged::Insert(kind, store, manifest, owner));
+        }
+        CommandAction::RemoveManifest(kind, name) => {
+          let store = self.get_store_kind(kind)?;
+          let owner = ObjectRef {
+            kind,
+            name: name.clone(),
+          };
+          let ownerships = owners.owned(&owner).cloned();
+          for owned in ownerships.into_iter().flatten() {
+            let owned_store = self.get_store_kind(owned.kind)?;
+            owners.release(owned.kind, &owned.name);
+            steps.push(Staged::Remove(owned.kind, owned_store, owned.name));
+          }
+          owners.release(kind, &name);
+          steps.push(Staged::Remove(kind, store, name));
+        }
+        action => {
+          return Err(Error::Conflict {
+            name: action.name().to_owned(),
+            reason: format!("{action:?} can't be batched"),
+          });
+        }
+      }
+    }
+
+    Ok(steps)
+  }
+
+  /// Computes what an insert or a removal would change, without applying it.
+  /// Inserts are admitted by the returned future, to be run off the command
+  /// loop like the admission of real writes.
+  fn plan(&self, action: CommandAction) -> Result<PlanFuture> {
+    match action {
+      CommandAction::InsertManifest(kind, manifest, owner) => {
+        let store = self.get_store_kind(kind)?;
+        if let Some(owner) = &owner {
+          self.owners.read().check(owner, kind, manifest.name())?;
+        }
+
+        // Writes are compared before admission, which unchanged ones skip.
+        let mut plan = store.plan_insert(&*manifest)?;
+        let admission = match plan.change {
+          PlannedChange::Unchanged => None,
+          _ => self.admissions.get(kind).cloned(),
+        };
+
+        Ok(Box::pin(async move {
+          if let Some(admission) = admission {
+            let manifest = admission.admit(manifest).await?;
+            if plan.change == PlannedChange::Update {
+              plan.diff = store.diff(&*manifest)?;
+            }
+          }
+
+          Ok(plan)
+        }))
+      }
+      CommandAction::RemoveManifest(kind, name) => {
+        let store = self.get_store_kind(kind)?;
+
+        let owner = ObjectRef {
+          kind,
+          name: name.clone(),
+        };
+        let mut cascade = Vec::new();
+        if let Some(ownerships) = self.owners.read().owned(&owner) {
+          for owned in ownerships {
+            if self.get_store_kind(owned.kind)?.contains(&owned.name) {
+              cascade.push(ObjectRef {
+                kind: owned.kind,
+                name: owned.name.clone(),
+              });
+            }
+          }
+        }
+
+        let change = match store.contains(&name) {
+          true => PlannedChange::Remove,
+          false => PlannedChange::Absent,
+        };
+
+        let plan = Plan {
+          kind,
+          name,
+          change,
+          diff: None,
+          cascade,
+        };
+        Ok(Box::pin(std::future::ready(Ok(plan))))
+      }
+      action => {
+        Err(Error::Conflict {
+          name: action.name().to_owned(),
+          reason: format!("{action:?} can't be dry run"),
+        })
+      }
+    }
+  }
+
+  fn operator_context(&self) -> OperatorContext {
+    OperatorContext {
+      command: self.command(),
+      metrics: self.metrics.clone(),
+      activity: self.activity.clone(),
+      faults: self.faults.clone(),
+      limiter: self.limiter.clone(),
+    }
+  }
+
   fn get_store_kind(&self, kind: ObjectKind) -> Result<Arc<DynStore>> {
     self
       .stores
       .get(kind)
       .cloned()
-      .ok_or_else(|| anyhow!("no store found for kind {}", kind))
+      .ok_or(Error::KindNotRegistered(kind))
+  }
+}
+
+/// Acknowledges a command, or logs its failure if it wasn't acknowledged.
+fn reply(ack: Option<CommandAck>, res: Result<CommandReply>) {
+  match ack {
+    Some(ack) => {
+      ack.send(res).ok();
+    }
+    None => {
+      if let Err(e) = res {
+        log::error!("{e}");
+      }
+    }
+  }
+}
+
+/// Waits until an operator is drained, or gone.
+async fn drained(mut drained_rx: watch::Receiver<bool>) {
+  while !*drained_rx.borrow() {
+    if drained_rx.changed().await.is_err() {
+      break;
+    }
   }
 }
 
 impl Default for Engine {
   fn default() -> Self {
-    let (command_tx, command_rx) = flume::unbounded();
+    let (command_tx, command_rx) = flume::unbounded::<CommandEvent>();
+
+    let metrics = Metrics::default();
+    let depth_rx = command_rx.clone();
+    metrics
+      .register_depth(vec![("channel", "command".to_owned())], move || {
+        depth_rx.len()
+      });
+
+    let (control_tx, control_rx) = flume::unbounded::<ControlFn>();
+    let depth_rx = control_rx.clone();
+    metrics
+      .register_depth(vec![("channel", "control".to_owned())], move || {
+        depth_rx.len()
+      });
+
+    let owners = Arc::<RwLock<Owners>>::default();
 
     Self {
       stores: Default::default(),
-      owners: Default::default(),
+      inspector: Inspector::new(owners.clone()),
+      owners,
+      admissions: Default::default(),
+      states: Default::default(),
+      operators: Default::default(),
+      draining: Default::default(),
+      watchers: Default::default(),
       start_queue: Default::default(),
+      started: false,
       command_tx,
       command_rx,
+      control_tx,
+      control_rx,
+      activity: Activity::new(metrics.clone()),
+      metrics,
+      events: Default::default(),
+      faults: Default::default(),
+      cache: Default::default(),
+      limiter: Default::default(),
     }
   }
 }
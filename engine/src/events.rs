@@ -0,0 +1,83 @@
+use std::{
+  collections::{BTreeMap, VecDeque}, sync::Arc
+};
+
+use flume::{Receiver, Sender};
+use gusto_core::{ObjectDefinition, ObjectEvent, ObjectKind, ObjectName};
+use parking_lot::RwLock;
+
+const MAX_EVENTS_PER_OBJECT: usize = 64;
+
+/// Registry
+#[derive(Default)]
+struct Registry {
+  objects: BTreeMap<(ObjectKind, ObjectName), VecDeque<ObjectEvent>>,
+  subscribers: Vec<Sender<ObjectEvent>>,
+}
+
+/// Events
+#[derive(Clone, Default)]
+pub struct Events {
+  inner: Arc<RwLock<Registry>>,
+}
+
+impl Events {
+  pub fn list<O>(&self, name: &ObjectName) -> Vec<ObjectEvent>
+  where
+    O: ObjectDefinition,
+  {
+    self.list_kind(O::kind(), name)
+  }
+
+  pub fn list_kind(
+    &self,
+    kind: ObjectKind,
+    name: &ObjectName,
+  ) -> Vec<ObjectEvent> {
+    self
+      .inner
+      .read()
+      .objects
+      .get(&(kind, name.to_owned()))
+      .map(|events| events.iter().cloned().collect())
+      .unwrap_or_default()
+  }
+
+  pub fn subscribe(&self) -> Receiver<ObjectEvent> {
+    let (tx, rx) = flume::unbounded();
+    self.inner.write().subscribers.push(tx);
+    rx
+  }
+
+  pub(crate) fn record(&self, event: ObjectEvent) {
+    let mut inner = self.inner.write();
+
+    let events = inner
+      .objects
+      .entry((event.kind, event.name.to_owned()))
+      .or_default();
+
+    let event = match events.iter().position(|e| e.is_similar(&event)) {
+      Some(i) => {
+        let mut existing = events.remove(i).unwrap();
+        existing.count += 1;
+        existing.last_timestamp = event.last_timestamp;
+        existing
+      }
+      None => event,
+    };
+
+    events.push_back(event.clone());
+    if events.len() > MAX_EVENTS_PER_OBJECT {
+      events.pop_front();
+    }
+
+    inner
+      .subscribers
+      .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+  }
+
+  pub(crate) fn forget(&self, kind: ObjectKind, name: &ObjectName) {
+    self.inner.write().objects.remove(&(kind, name.to_owned()));
+  }
+}
@@ -0,0 +1,92 @@
+use std::{sync::Arc, time::Duration};
+
+use flume::{Receiver, Sender};
+use parking_lot::Mutex;
+
+/// FaultConfig
+#[derive(Clone, Debug, Default)]
+pub struct FaultConfig {
+  /// Seed of the random generator deciding which faults are injected.
+  pub seed: u64,
+  /// Fraction of writes that fail, between 0 and 1: inserts, removals,
+  /// patches, and batches as a whole.
+  pub write_failure_rate: f64,
+  /// Upper bound of the random delay added before delivering a store event.
+  pub max_event_delay: Duration,
+}
+
+/// FaultState
+struct FaultState {
+  config: FaultConfig,
+  rng: SplitMix64,
+}
+
+/// Faults
+///
+/// Deterministically injects failures into the engine, to check that
+/// controllers are idempotent and converge.
+#[derive(Clone, Default)]
+pub struct Faults {
+  state: Arc<Mutex<Option<FaultState>>>,
+  restarts: Arc<Mutex<Vec<Sender<()>>>>,
+}
+
+impl Faults {
+  pub fn enable(&self, config: FaultConfig) {
+    *self.state.lock() = Some(FaultState {
+      rng: SplitMix64(config.seed),
+      config,
+    });
+  }
+
+  pub fn disable(&self) {
+    *self.state.lock() = None;
+  }
+
+  /// Simulates an engine restart: every operator cancels its reconciles,
+  /// drops its objects and rebuilds them from the store, initializing their
+  /// state from scratch.
+  pub fn restart(&self) {
+    self
+      .restarts
+      .lock()
+      .retain(|restart_tx| restart_tx.send(()).is_ok());
+  }
+
+  pub(crate) fn restarts(&self) -> Receiver<()> {
+    let (restart_tx, restart_rx) = flume::unbounded();
+    self.restarts.lock().push(restart_tx);
+    restart_rx
+  }
+
+  pub(crate) fn fail_write(&self) -> bool {
+    self.state.lock().as_mut().is_some_and(|state| {
+      let rate = state.config.write_failure_rate;
+      rate > 0.0 && state.rng.next_f64() < rate
+    })
+  }
+
+  pub(crate) fn event_delay(&self) -> Option<Duration> {
+    self.state.lock().as_mut().and_then(|state| {
+      let max = state.config.max_event_delay;
+      (!max.is_zero()).then(|| max.mul_f64(state.rng.next_f64()))
+    })
+  }
+}
+
+/// SplitMix64
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+  fn next_u64(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+  }
+
+  fn next_f64(&mut self) -> f64 {
+    (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+  }
+}
@@ -0,0 +1,129 @@
+use flume::Sender;
+use gusto_core::{Controller, Error, ObjectDefinition, Result};
+use tokio::sync::oneshot;
+
+use crate::{engine::ControlFn, ControllerOptions, Engine, ObjectOptions};
+
+/// EngineHandle
+///
+/// Registers kinds and controllers on a running engine, e.g. for plugins
+/// that come and go.
+#[derive(Clone)]
+pub struct EngineHandle {
+  control_tx: Sender<ControlFn>,
+}
+
+impl EngineHandle {
+  pub(crate) fn new(control_tx: Sender<ControlFn>) -> Self {
+    Self { control_tx }
+  }
+
+  pub async fn register_object<O>(&self) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .register_object_with::<O>(ObjectOptions::default())
+      .await
+  }
+
+  pub async fn register_object_with<O>(
+    &self,
+    options: ObjectOptions<O>,
+  ) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .call(move |engine| engine.register_object_with(options))
+      .await
+  }
+
+  pub async fn register_controller<O>(
+    &self,
+    controller: impl Controller<O>,
+  ) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .register_controller_with(controller, ControllerOptions::default())
+      .await
+  }
+
+  /// Registers the controller of a kind, which takes over the objects
+  /// already stored.
+  pub async fn register_controller_with<O>(
+    &self,
+    controller: impl Controller<O>,
+    options: ControllerOptions<O>,
+  ) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .call(move |engine| engine.register_controller_with(controller, options))
+      .await?
+  }
+
+  pub async fn replace_controller<O>(
+    &self,
+    controller: impl Controller<O>,
+  ) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .replace_controller_with(controller, ControllerOptions::default())
+      .await
+  }
+
+  /// Replaces the controller of a kind. The old controller finishes its
+  /// reconciles in flight and drops the queued ones, then the new one takes
+  /// over the objects. Returns once the old controller is drained.
+  pub async fn replace_controller_with<O>(
+    &self,
+    controller: impl Controller<O>,
+    options: ControllerOptions<O>,
+  ) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    let replaced = self
+      .call(move |engine| engine.replace_controller_with(controller, options))
+      .await??;
+    replaced.await;
+
+    Ok(())
+  }
+
+  /// Unregisters the controller of a kind once its reconciles in flight are
+  /// finished. Objects are kept, and terminated ones are released.
+  pub async fn unregister_controller<O>(&self) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    let drained = self
+      .call(|engine| engine.unregister_controller::<O>())
+      .await??;
+    drained.await;
+
+    Ok(())
+  }
+
+  /// Runs a function on the engine, between two commands.
+  async fn call<R>(
+    &self,
+    f: impl FnOnce(&mut Engine) -> R + Send + 'static,
+  ) -> Result<R>
+  where
+    R: Send + 'static,
+  {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    self.control_tx.send(Box::new(move |engine| {
+      reply_tx.send(f(engine)).ok();
+    }))?;
+
+    reply_rx.await.map_err(|_| Error::Closed)
+  }
+}
@@ -1,14 +1,29 @@
 #![allow(incomplete_features)]
 #![feature(box_into_inner)]
-#![feature(trait_upcasting)]
 
+#[cfg(feature = "serde")]
+pub use self::checkpoint::*;
 pub use self::{
-  engine::*, object::*, operator::*, ownership::*, reconciler::*, store::*
+  activity::*, engine::*, events::*, faults::*, handle::*, limiter::*, metrics::*, object::*, operator::*, options::*, ownership::*, queue::*, reconciler::*, snapshot::*, state::*, store::*
 };
 
+mod activity;
+mod cache;
+#[cfg(feature = "serde")]
+mod checkpoint;
 mod engine;
+mod events;
+mod faults;
+mod handle;
+mod limiter;
+mod log;
+mod metrics;
 mod object;
 mod operator;
+mod options;
 mod ownership;
+mod queue;
 mod reconciler;
+mod snapshot;
+mod state;
 mod store;
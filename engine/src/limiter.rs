@@ -0,0 +1,195 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use crate::ObjectId;
+
+/// Consecutive fast requeues after which an object is considered hot.
+const HOT_LOOP_COUNT: u32 = 5;
+const HOT_LOOP_MAX_DELAY: Duration = Duration::from_secs(300);
+/// Longest a rate-limited reconcile waits before checking the limits again.
+const RATE_LIMIT_MAX_DELAY: Duration = Duration::from_secs(300);
+
+/// RateLimit
+///
+/// Token bucket refilled at `per_second` tokens per second, holding up to
+/// `burst` tokens. Each reconcile takes one token. A rate that isn't
+/// positive, e.g. zero, means unlimited.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+  pub per_second: f64,
+  pub burst: u32,
+}
+
+impl RateLimit {
+  pub fn new(per_second: f64, burst: u32) -> Self {
+    Self {
+      per_second,
+      burst: burst.max(1),
+    }
+  }
+}
+
+/// TokenBucket
+struct TokenBucket {
+  limit: RateLimit,
+  tokens: f64,
+  updated: Instant,
+}
+
+impl TokenBucket {
+  fn new(limit: RateLimit) -> Self {
+    Self {
+      limit,
+      tokens: limit.burst as f64,
+      updated: Instant::now(),
+    }
+  }
+
+  fn is_unlimited(&self) -> bool {
+    self.limit.per_second.is_nan() || self.limit.per_second <= 0.0
+  }
+
+  /// Refills the bucket, returning how long until it holds a token.
+  fn wait(&mut self, now: Instant) -> Duration {
+    if self.is_unlimited() {
+      return Duration::ZERO;
+    }
+
+    let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+    self.tokens = (self.tokens + elapsed * self.limit.per_second)
+      .min(self.limit.burst as f64);
+    self.updated = now;
+
+    let deficit = 1.0 - self.tokens;
+    match deficit > 0.0 {
+      true => {
+        Duration::try_from_secs_f64(deficit / self.limit.per_second)
+          .unwrap_or(Duration::MAX)
+      }
+      false => Duration::ZERO,
+    }
+  }
+
+  fn take(&mut self) {
+    if !self.is_unlimited() {
+      self.tokens -= 1.0;
+    }
+  }
+}
+
+/// Limiter
+///
+/// Rate limit shared by every controller of an engine.
+#[derive(Clone, Default)]
+pub(crate) struct Limiter {
+  bucket: Arc<Mutex<Option<TokenBucket>>>,
+}
+
+impl Limiter {
+  pub fn set(&self, limit: RateLimit) {
+    *self.bucket.lock() = Some(TokenBucket::new(limit));
+  }
+}
+
+/// HotLoop
+pub(crate) struct HotLoop {
+  /// Delay to requeue the object after instead of the requested one.
+  pub delay: Duration,
+  /// Whether the object just became hot.
+  pub detected: bool,
+}
+
+/// Limits
+///
+/// Rate limits and hot-loop protection applied to the reconciles of a
+/// controller.
+pub(crate) struct Limits {
+  global: Limiter,
+  controller: Option<Mutex<TokenBucket>>,
+  object: Option<RateLimit>,
+  objects: Mutex<HashMap<ObjectId, TokenBucket>>,
+  hot_loop_threshold: Option<Duration>,
+  fast_requeues: Mutex<HashMap<ObjectId, u32>>,
+}
+
+impl Limits {
+  pub fn new(
+    global: Limiter,
+    controller: Option<RateLimit>,
+    object: Option<RateLimit>,
+    hot_loop_threshold: Option<Duration>,
+  ) -> Self {
+    Self {
+      global,
+      controller: controller.map(|limit| Mutex::new(TokenBucket::new(limit))),
+      object,
+      objects: Default::default(),
+      hot_loop_threshold,
+      fast_requeues: Default::default(),
+    }
+  }
+
+  /// Takes a token from every bucket if they all hold one. Otherwise takes
+  /// none, and returns how long to wait before trying again.
+  pub fn try_reserve(&self, id: ObjectId) -> Result<(), Duration> {
+    let now = Instant::now();
+    let mut global = self.global.bucket.lock();
+    let mut controller = self.controller.as_ref().map(|bucket| bucket.lock());
+    let mut objects = self.objects.lock();
+    let object = self.object.map(|limit| {
+      objects.entry(id).or_insert_with(|| TokenBucket::new(limit))
+    });
+
+    let mut buckets = [global.as_mut(), controller.as_deref_mut(), object];
+    let wait = buckets
+      .iter_mut()
+      .flatten()
+      .map(|bucket| bucket.wait(now))
+      .max()
+      .unwrap_or_default();
+    if !wait.is_zero() {
+      return Err(wait.min(RATE_LIMIT_MAX_DELAY));
+    }
+
+    for bucket in buckets.into_iter().flatten() {
+      bucket.take();
+    }
+    Ok(())
+  }
+
+  /// Tracks requeues shorter than the hot-loop threshold. Once an object
+  /// keeps requeueing that fast, returns the backoff to requeue it after
+  /// instead, doubling on each further fast requeue.
+  pub fn throttle(&self, id: ObjectId, requeue: Duration) -> Option<HotLoop> {
+    let threshold = self.hot_loop_threshold?;
+    let mut fast_requeues = self.fast_requeues.lock();
+
+    if requeue >= threshold {
+      fast_requeues.remove(&id);
+      return None;
+    }
+
+    let count = fast_requeues.entry(id).or_default();
+    *count += 1;
+    let excess = count.checked_sub(HOT_LOOP_COUNT)?;
+
+    Some(HotLoop {
+      delay: threshold
+        .saturating_mul(2u32.saturating_pow(excess))
+        .min(HOT_LOOP_MAX_DELAY),
+      detected: excess == 0,
+    })
+  }
+
+  /// Resets hot-loop tracking, e.g. when the requeue chain is broken.
+  pub fn cool_down(&self, id: &ObjectId) {
+    self.fast_requeues.lock().remove(id);
+  }
+
+  pub fn forget(&self, id: &ObjectId) {
+    self.objects.lock().remove(id);
+    self.fast_requeues.lock().remove(id);
+  }
+}
@@ -0,0 +1,62 @@
+//! Internal logging facade, backed by `tracing` when the feature is enabled.
+
+#[cfg(not(feature = "tracing"))]
+use std::future::Future;
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Instrument;
+
+macro_rules! debug {
+  ($($arg:tt)*) => {
+    #[cfg(feature = "tracing")]
+    tracing::debug!($($arg)*);
+  };
+}
+
+macro_rules! error {
+  ($($arg:tt)*) => {
+    #[cfg(feature = "tracing")]
+    tracing::error!($($arg)*);
+    #[cfg(not(feature = "tracing"))]
+    eprintln!($($arg)*);
+  };
+}
+
+macro_rules! warning {
+  ($($arg:tt)*) => {
+    #[cfg(feature = "tracing")]
+    tracing::warn!($($arg)*);
+    #[cfg(not(feature = "tracing"))]
+    eprintln!($($arg)*);
+  };
+}
+
+macro_rules! span {
+  ($($arg:tt)*) => {{
+    #[cfg(feature = "tracing")]
+    let span = tracing::info_span!($($arg)*);
+    #[cfg(not(feature = "tracing"))]
+    let span = $crate::log::Span;
+    span
+  }};
+}
+
+pub(crate) use debug;
+pub(crate) use error;
+pub(crate) use span;
+pub(crate) use warning;
+
+/// Span
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Span;
+
+/// Instrument
+#[cfg(not(feature = "tracing"))]
+pub(crate) trait Instrument: Sized {
+  fn instrument(self, _span: Span) -> Self {
+    self
+  }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl<T> Instrument for T where T: Future {}
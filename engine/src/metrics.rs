@@ -0,0 +1,363 @@
+use std::{
+  collections::BTreeMap, fmt::{Display, Write}, sync::{
+    atomic::{AtomicU64, Ordering}, Arc
+  }, time::Duration
+};
+
+use gusto_core::ObjectKind;
+use parking_lot::RwLock;
+
+const BUCKETS: [f64; 11] = [
+  0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+const COMMANDS: Metric =
+  ("gusto_commands_total", "Commands processed by the engine.");
+const COMMAND_ERRORS: Metric = (
+  "gusto_command_errors_total",
+  "Commands that failed to apply.",
+);
+const UNCHANGED_WRITES: Metric = (
+  "gusto_unchanged_writes_total",
+  "Inserts skipped because the manifest didn't change.",
+);
+const RECONCILE_DURATION: Metric = (
+  "gusto_reconcile_duration_seconds",
+  "Duration of reconciles.",
+);
+const RECONCILE_ERRORS: Metric = (
+  "gusto_reconcile_errors_total",
+  "Reconciles that returned an error.",
+);
+const REQUEUES: Metric = (
+  "gusto_requeues_total",
+  "Reconciles that requested a requeue.",
+);
+const RATE_LIMITED: Metric = (
+  "gusto_rate_limited_total",
+  "Reconciles delayed by a rate limit.",
+);
+const HOT_LOOPS: Metric = (
+  "gusto_hot_loops_total",
+  "Requeues throttled because the object requeued in a hot loop.",
+);
+const IN_FLIGHT: Metric = (
+  "gusto_reconciles_in_flight",
+  "Reconciles currently running.",
+);
+const CHANNEL_DEPTH: Metric =
+  ("gusto_channel_depth", "Events waiting in a channel.");
+
+/// Name and help of a metric.
+type Metric = (&'static str, &'static str);
+type Labels = Vec<(&'static str, String)>;
+type Family<V> = RwLock<BTreeMap<Labels, V>>;
+type DepthFn = Box<dyn Fn() -> usize + Send + Sync>;
+
+/// DepthProbe
+///
+/// Identifies a registered depth probe, so it can be unregistered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct DepthProbe(u64);
+
+/// Histogram
+#[derive(Default)]
+struct Histogram {
+  buckets: [u64; BUCKETS.len()],
+  sum: f64,
+  count: u64,
+}
+
+impl Histogram {
+  fn observe(&mut self, value: f64) {
+    for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+      if value <= bound {
+        *bucket += 1;
+      }
+    }
+    self.sum += value;
+    self.count += 1;
+  }
+}
+
+/// Registry
+#[derive(Default)]
+struct Registry {
+  commands: Family<u64>,
+  command_errors: Family<u64>,
+  unchanged_writes: Family<u64>,
+  reconcile_duration: Family<Histogram>,
+  reconcile_errors: Family<u64>,
+  requeues: Family<u64>,
+  rate_limited: Family<u64>,
+  hot_loops: Family<u64>,
+  in_flight: Family<i64>,
+  depths: RwLock<Vec<(DepthProbe, Labels, DepthFn)>>,
+  next_probe: AtomicU64,
+}
+
+/// Metrics
+///
+/// Rendered in the Prometheus text format. With the `metrics` feature, also
+/// emitted through the `metrics` facade, to the installed recorder.
+#[derive(Clone, Default)]
+pub struct Metrics {
+  inner: Arc<Registry>,
+}
+
+impl Metrics {
+  pub fn render(&self) -> String {
+    let mut out = String::new();
+    let r = &self.inner;
+
+    render_counter(&mut out, COMMANDS, &r.commands);
+    render_counter(&mut out, COMMAND_ERRORS, &r.command_errors);
+    render_counter(&mut out, UNCHANGED_WRITES, &r.unchanged_writes);
+    render_histogram(&mut out, RECONCILE_DURATION, &r.reconcile_duration);
+    render_counter(&mut out, RECONCILE_ERRORS, &r.reconcile_errors);
+    render_counter(&mut out, REQUEUES, &r.requeues);
+    render_counter(&mut out, RATE_LIMITED, &r.rate_limited);
+    render_counter(&mut out, HOT_LOOPS, &r.hot_loops);
+    render_gauge(
+      &mut out,
+      IN_FLIGHT,
+      r.in_flight
+        .read()
+        .iter()
+        .map(|(l, v)| (l.clone(), *v as f64)),
+    );
+    render_gauge(&mut out, CHANNEL_DEPTH, self.depths().into_iter());
+
+    out
+  }
+
+  /// Describes the metrics to the installed recorder.
+  #[cfg(feature = "metrics")]
+  pub fn describe() {
+    for (name, help) in [
+      COMMANDS,
+      COMMAND_ERRORS,
+      UNCHANGED_WRITES,
+      RECONCILE_ERRORS,
+      REQUEUES,
+      RATE_LIMITED,
+      HOT_LOOPS,
+    ] {
+      ::metrics::describe_counter!(name, help);
+    }
+    let (name, help) = RECONCILE_DURATION;
+    ::metrics::describe_histogram!(name, ::metrics::Unit::Seconds, help);
+    for (name, help) in [IN_FLIGHT, CHANNEL_DEPTH] {
+      ::metrics::describe_gauge!(name, help);
+    }
+  }
+
+  /// Sets the channel depth gauges of the installed recorder. Depths are
+  /// sampled rather than pushed, so exporters call this before each scrape.
+  #[cfg(feature = "metrics")]
+  pub fn record_depths(&self) {
+    for (labels, depth) in self.depths() {
+      ::metrics::gauge!(CHANNEL_DEPTH.0, labels.as_slice()).set(depth);
+    }
+  }
+
+  fn depths(&self) -> Vec<(Labels, f64)> {
+    self
+      .inner
+      .depths
+      .read()
+      .iter()
+      .map(|(_, labels, depth)| (labels.clone(), depth() as f64))
+      .collect()
+  }
+
+  /// Returns the total number of events waiting in channels.
+  pub fn queued(&self) -> usize {
+    self
+      .inner
+      .depths
+      .read()
+      .iter()
+      .map(|(_, _, depth)| depth())
+      .sum()
+  }
+
+  pub(crate) fn register_depth(
+    &self,
+    labels: Labels,
+    depth: impl Fn() -> usize + Send + Sync + 'static,
+  ) -> DepthProbe {
+    let probe =
+      DepthProbe(self.inner.next_probe.fetch_add(1, Ordering::Relaxed));
+    self
+      .inner
+      .depths
+      .write()
+      .push((probe, labels, Box::new(depth)));
+    probe
+  }
+
+  /// Drops a probe, e.g. once its channel is no longer consumed.
+  pub(crate) fn unregister_depth(&self, probe: DepthProbe) {
+    self.inner.depths.write().retain(|(p, ..)| *p != probe);
+  }
+
+  pub(crate) fn command(&self, action: &str, kind: ObjectKind, ok: bool) {
+    let labels = vec![("action", action.to_owned()), ("kind", kind.to_owned())];
+
+    if !ok {
+      count(COMMAND_ERRORS, &self.inner.command_errors, labels.clone());
+    }
+    count(COMMANDS, &self.inner.commands, labels);
+  }
+
+  pub(crate) fn unchanged_write(&self, kind: ObjectKind) {
+    count(
+      UNCHANGED_WRITES,
+      &self.inner.unchanged_writes,
+      kind_labels(kind),
+    );
+  }
+
+  pub(crate) fn rate_limited(&self, kind: ObjectKind) {
+    count(RATE_LIMITED, &self.inner.rate_limited, kind_labels(kind));
+  }
+
+  pub(crate) fn hot_loop(&self, kind: ObjectKind) {
+    count(HOT_LOOPS, &self.inner.hot_loops, kind_labels(kind));
+  }
+
+  pub(crate) fn reconcile_started(&self, kind: ObjectKind) {
+    let labels = kind_labels(kind);
+    #[cfg(feature = "metrics")]
+    ::metrics::gauge!(IN_FLIGHT.0, labels.as_slice()).increment(1.0);
+    *self.inner.in_flight.write().entry(labels).or_default() += 1;
+  }
+
+  pub(crate) fn reconcile_finished(
+    &self,
+    kind: ObjectKind,
+    duration: Duration,
+    ok: bool,
+    requeue: bool,
+  ) {
+    let labels = kind_labels(kind);
+    let seconds = duration.as_secs_f64();
+
+    #[cfg(feature = "metrics")]
+    {
+      ::metrics::gauge!(IN_FLIGHT.0, labels.as_slice()).decrement(1.0);
+      ::metrics::histogram!(RECONCILE_DURATION.0, labels.as_slice())
+        .record(seconds);
+    }
+    *self
+      .inner
+      .in_flight
+      .write()
+      .entry(labels.clone())
+      .or_default() -= 1;
+    self
+      .inner
+      .reconcile_duration
+      .write()
+      .entry(labels.clone())
+      .or_default()
+      .observe(seconds);
+
+    if !ok {
+      count(
+        RECONCILE_ERRORS,
+        &self.inner.reconcile_errors,
+        labels.clone(),
+      );
+    }
+    if requeue {
+      count(REQUEUES, &self.inner.requeues, labels);
+    }
+  }
+}
+
+impl Display for Metrics {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.render())
+  }
+}
+
+/// Increments a counter, and its counterpart in the installed recorder.
+fn count((_name, _): Metric, family: &Family<u64>, labels: Labels) {
+  #[cfg(feature = "metrics")]
+  ::metrics::counter!(_name, labels.as_slice()).increment(1);
+  *family.write().entry(labels).or_default() += 1;
+}
+
+fn kind_labels(kind: ObjectKind) -> Labels {
+  vec![("kind", kind.to_owned())]
+}
+
+fn render_labels(labels: &Labels, extra: Option<(&str, String)>) -> String {
+  let pairs = labels
+    .iter()
+    .map(|(k, v)| (*k, v.as_str()))
+    .chain(extra.as_ref().map(|(k, v)| (*k, v.as_str())))
+    .map(|(k, v)| {
+      let v = v
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+      format!("{k}=\"{v}\"")
+    })
+    .collect::<Vec<_>>();
+
+  if pairs.is_empty() {
+    String::new()
+  } else {
+    format!("{{{}}}", pairs.join(","))
+  }
+}
+
+fn render_header(out: &mut String, name: &str, help: &str, ty: &str) {
+  writeln!(out, "# HELP {name} {help}").ok();
+  writeln!(out, "# TYPE {name} {ty}").ok();
+}
+
+fn render_counter(
+  out: &mut String,
+  (name, help): Metric,
+  family: &Family<u64>,
+) {
+  render_header(out, name, help, "counter");
+  for (labels, value) in family.read().iter() {
+    writeln!(out, "{name}{} {value}", render_labels(labels, None)).ok();
+  }
+}
+
+fn render_gauge(
+  out: &mut String,
+  (name, help): Metric,
+  values: impl Iterator<Item = (Labels, f64)>,
+) {
+  render_header(out, name, help, "gauge");
+  for (labels, value) in values {
+    writeln!(out, "{name}{} {value}", render_labels(&labels, None)).ok();
+  }
+}
+
+fn render_histogram(
+  out: &mut String,
+  (name, help): Metric,
+  family: &Family<Histogram>,
+) {
+  render_header(out, name, help, "histogram");
+  for (labels, histogram) in family.read().iter() {
+    for (count, bound) in histogram.buckets.iter().zip(BUCKETS) {
+      let le = render_labels(labels, Some(("le", bound.to_string())));
+      writeln!(out, "{name}_bucket{le} {count}").ok();
+    }
+    let le = render_labels(labels, Some(("le", "+Inf".to_owned())));
+    writeln!(out, "{name}_bucket{le} {}", histogram.count).ok();
+
+    let labels = render_labels(labels, None);
+    writeln!(out, "{name}_sum{labels} {}", histogram.sum).ok();
+    writeln!(out, "{name}_count{labels} {}", histogram.count).ok();
+  }
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use gusto_core::{ObjectDefinition, ObjectManifest, ObjectName};
+use gusto_core::{ObjectDefinition, ObjectManifest, ObjectName, ObjectRef};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
@@ -31,6 +31,13 @@ where
   pub fn name(&self) -> &ObjectName {
     &self.manifest.meta.name
   }
+
+  pub fn object_ref(&self) -> ObjectRef {
+    ObjectRef {
+      kind: O::kind(),
+      name: self.name().to_owned(),
+    }
+  }
 }
 
 impl<O> Clone for Object<O>
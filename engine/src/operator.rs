@@ -1,14 +1,16 @@
 use std::{
-  collections::{btree_map::Entry, BTreeMap}, sync::Arc
+  collections::{btree_map::Entry, BTreeMap, BTreeSet, VecDeque}, marker::PhantomData, sync::Arc
 };
 
-use anyhow::{bail, Result};
+use flume::{Receiver, Sender};
 use gusto_core::{
-  Command, Controller, ObjectDefinition, ObjectManifest, ObjectName
+  util::Safe, Command, Condition, ConditionStatus, Controller, DynObjectManifest, Error, ObjectDefinition, ObjectManifest, ObjectName, Result
 };
+use parking_lot::RwLock;
+use tokio::{sync::watch, time::Instant};
 
 use crate::{
-  store::{Change, StoreEvent}, Object, ObjectId, Reconciler, Store
+  activity::ActivityGuard, log::{self, Instrument}, metrics::DepthProbe, reconciler::retry_delay, snapshot::{Inspect, OperatorObject}, state::{AnyStates, PersistedStates, SharedStates}, store::{Change, StoreEvent}, Activity, Faults, Limiter, Metrics, Object, ObjectId, Priority, ReconcileOptions, Reconciler, SnapshotFn, Store, Tracker
 };
 
 /// Objects
@@ -31,6 +33,8 @@ where
         entry.insert(object);
       }
       Entry::Occupied(mut entry) => {
+        self.id_index.remove(&entry.get().id);
+        self.id_index.insert(object.id, object.name().to_owned());
         entry.insert(object);
       }
     }
@@ -52,8 +56,23 @@ where
     }
   }
 
+  pub fn get(&self, name: &ObjectName) -> Option<&Object<O>> {
+    self.inner.get(name)
+  }
+
+  pub fn get_by_id(&self, id: &ObjectId) -> Option<&Object<O>> {
+    self.id_index.get(id).and_then(|name| self.inner.get(name))
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &Object<O>> {
+    self.inner.values()
+  }
+
   pub fn remove(&mut self, name: &ObjectName) -> Option<Object<O>> {
-    self.inner.remove(name)
+    let object = self.inner.remove(name)?;
+    self.id_index.remove(&object.id);
+
+    Some(object)
   }
 }
 
@@ -69,6 +88,91 @@ where
   }
 }
 
+/// OperatorInspector
+struct OperatorInspector<O>
+where
+  O: ObjectDefinition,
+{
+  objects: Arc<RwLock<Objects<O>>>,
+  tracker: Tracker<O>,
+}
+
+impl<O> Inspect for OperatorInspector<O>
+where
+  O: ObjectDefinition,
+{
+  fn objects(&self) -> BTreeMap<ObjectName, OperatorObject> {
+    self
+      .objects
+      .read()
+      .iter()
+      .map(|object| {
+        let reconcile = self.tracker.state(&object.id);
+        (
+          object.name().to_owned(),
+          OperatorObject {
+            id: object.id,
+            reconcile,
+          },
+        )
+      })
+      .collect()
+  }
+}
+
+/// Admission
+///
+/// Runs a controller's admission outside of its operator, for dry runs.
+#[async_trait::async_trait]
+pub(crate) trait Admission: Safe {
+  async fn admit(
+    &self,
+    manifest: Box<DynObjectManifest>,
+  ) -> Result<Box<DynObjectManifest>>;
+}
+
+/// ControllerAdmission
+struct ControllerAdmission<C, O> {
+  controller: Arc<C>,
+  _object: PhantomData<O>,
+}
+
+#[async_trait::async_trait]
+impl<C, O> Admission for ControllerAdmission<C, O>
+where
+  C: Controller<O>,
+  O: ObjectDefinition,
+{
+  async fn admit(
+    &self,
+    manifest: Box<DynObjectManifest>,
+  ) -> Result<Box<DynObjectManifest>> {
+    let manifest = Box::into_inner(manifest.as_manifest::<O>()?);
+    let name = manifest.name().to_owned();
+
+    let manifest = self
+      .controller
+      .admit_manifest(manifest)
+      .await
+      .map_err(|e| Error::admission_denied(&name, e))?;
+
+    Ok(Box::new(manifest))
+  }
+}
+
+/// OperatorContext
+#[derive(Clone)]
+pub struct OperatorContext {
+  pub command: Command,
+  pub metrics: Metrics,
+  pub activity: Activity,
+  pub faults: Faults,
+  pub(crate) limiter: Limiter,
+}
+
+/// Stops an operator, which flags once it's drained.
+pub(crate) type StopSender = Sender<watch::Sender<bool>>;
+
 /// Operator
 pub struct Operator<C, O>
 where
@@ -77,8 +181,51 @@ where
 {
   controller: Arc<C>,
   reconciler: Reconciler<C, O>,
-  objects: Objects<O>,
+  objects: Arc<RwLock<Objects<O>>>,
   store: Arc<Store<O>>,
+  context: OperatorContext,
+  requeue_rx: Receiver<(ObjectId, Priority)>,
+  trigger_tx: Sender<ObjectName>,
+  trigger_rx: Receiver<ObjectName>,
+  restart_rx: Receiver<()>,
+  /// Store events held back by injected faults, with their delivery time.
+  delayed: VecDeque<(Instant, StoreEvent<O>, ActivityGuard)>,
+  states: Option<Arc<SharedStates<O>>>,
+  persisted: Option<Arc<PersistedStates<O>>>,
+  failures: BTreeMap<ObjectName, Failure>,
+  retries: u64,
+  retry_tx: Sender<(ObjectName, u64)>,
+  retry_rx: Receiver<(ObjectName, u64)>,
+  stop_tx: StopSender,
+  stop_rx: Receiver<watch::Sender<bool>>,
+  probes: Vec<DepthProbe>,
+}
+
+/// Failure
+///
+/// Admission or initialization of an object that failed. Denied manifests
+/// wait for an update, other failures are retried.
+#[derive(Default)]
+struct Failure {
+  attempts: u32,
+  /// Token of the pending retry, so that timers of earlier failures are
+  /// ignored.
+  retry: Option<u64>,
+  /// Generation whose admission was denied, recorded only once.
+  denied: Option<u64>,
+}
+
+/// Failed
+///
+/// Error of the step at which admitting, initializing or accepting an object
+/// failed.
+struct Failed {
+  reason: &'static str,
+  error: Error,
+}
+
+fn failed(reason: &'static str) -> impl FnOnce(Error) -> Failed {
+  move |error| Failed { reason, error }
 }
 
 impl<C, O> Operator<C, O>
@@ -86,74 +233,574 @@ where
   C: Controller<O>,
   O: ObjectDefinition,
 {
-  pub fn new(controller: C, command: Command, store: Arc<Store<O>>) -> Self {
+  pub(crate) fn new(
+    controller: C,
+    store: Arc<Store<O>>,
+    context: OperatorContext,
+    options: ReconcileOptions,
+    share_state: Option<SnapshotFn<O>>,
+    persist_state: Option<PersistedStates<O>>,
+  ) -> Self {
     let controller = Arc::new(controller);
+    let states =
+      share_state.map(|snapshot| Arc::new(SharedStates::new(snapshot)));
+    let persisted = persist_state.map(Arc::new);
+    store.set_managed(true);
+
+    let (requeue_tx, requeue_rx) = flume::unbounded();
+    let (retry_tx, retry_rx) = flume::unbounded();
+    let (trigger_tx, trigger_rx) = flume::unbounded();
+    let (stop_tx, stop_rx) = flume::unbounded();
 
     Self {
       controller: controller.clone(),
-      reconciler: Reconciler::new(command, controller),
+      reconciler: Reconciler::new(
+        controller,
+        store.clone(),
+        &context,
+        options,
+        states.clone(),
+        persisted.clone(),
+        requeue_tx,
+      ),
       objects: Default::default(),
       store,
+      restart_rx: context.faults.restarts(),
+      delayed: Default::default(),
+      context,
+      requeue_rx,
+      trigger_tx,
+      trigger_rx,
+      states,
+      persisted,
+      failures: Default::default(),
+      retries: 0,
+      retry_tx,
+      retry_rx,
+      stop_tx,
+      stop_rx,
+      probes: Vec::new(),
     }
   }
 
+  /// Returns a sender of names of objects to reconcile, used to watch other
+  /// kinds.
+  pub(crate) fn trigger(&self) -> Sender<ObjectName> {
+    self.trigger_tx.clone()
+  }
+
+  pub(crate) fn stopper(&self) -> StopSender {
+    self.stop_tx.clone()
+  }
+
+  pub(crate) fn admission(&self) -> Arc<dyn Admission> {
+    Arc::new(ControllerAdmission {
+      controller: self.controller.clone(),
+      _object: PhantomData,
+    })
+  }
+
+  pub(crate) fn states(&self) -> Option<Arc<dyn AnyStates>> {
+    self
+      .states
+      .clone()
+      .map(|states| states as Arc<dyn AnyStates>)
+  }
+
+  pub(crate) fn inspector(&self) -> Arc<dyn Inspect> {
+    Arc::new(OperatorInspector {
+      objects: self.objects.clone(),
+      tracker: self.reconciler.tracker(),
+    })
+  }
+
   pub async fn start(&mut self) {
     let events_rx = self.store.events();
+    let requeue_rx = self.requeue_rx.clone();
+    let trigger_rx = self.trigger_rx.clone();
+    let restart_rx = self.restart_rx.clone();
+    let retry_rx = self.retry_rx.clone();
+    let stop_rx = self.stop_rx.clone();
+
+    self.register_probes();
+    self.resume().await;
 
-    while let Ok(event) = events_rx.recv_async().await {
-      println!("received store event: {:?}", event.change);
+    loop {
+      let delivery = self.delayed.front().map(|(at, ..)| *at);
+
+      tokio::select! {
+        event = events_rx.recv_async() => match event {
+          Ok(event) => self.handle_store_event(event).await,
+          Err(_) => break,
+        },
+        () = tokio::time::sleep_until(delivery.unwrap_or_else(Instant::now)),
+          if delivery.is_some() =>
+        {
+          if let Some((_, event, _activity)) = self.delayed.pop_front() {
+            self.deliver(event).await;
+          }
+        }
+        Ok((id, priority)) = requeue_rx.recv_async() => {
+          self.handle_requeue(id, priority)
+        }
+        Ok(name) = trigger_rx.recv_async() => self.handle_trigger(name),
+        Ok(()) = restart_rx.recv_async() => self.restart().await,
+        Ok((name, token)) = retry_rx.recv_async() => {
+          self.handle_retry(name, token).await
+        }
+        Ok(stopped_tx) = stop_rx.recv_async() => {
+          self.drain().await;
+          stopped_tx.send_replace(true);
+          break;
+        }
+      }
+    }
+  }
+
+  /// Takes over the objects already in the store, e.g. when the controller
+  /// is registered on a running engine or replaces another one. Objects a
+  /// previous controller accepted are created like on a restart, others are
+  /// admitted first. Pending events are superseded by the store, except for
+  /// objects removed meanwhile, which are restored or initialized only to be
+  /// terminated.
+  async fn resume(&mut self) {
+    let _activity = self.context.activity.enter();
+
+    let events = self.store.events().drain().collect::<Vec<_>>();
+    for event in events {
+      let name = event.manifest.name().to_owned();
+      if !matches!(event.change, Change::Delete) || self.store.contains(&name) {
+        continue;
+      }
+
+      log::debug!(kind = O::kind(), name = %name, "terminate removed object");
+      let state = match self.restore_state(&name).await {
+        Some(state) => Ok(state),
+        None => self.controller.initialize_state(&event.manifest).await,
+      };
+      match state {
+        Ok(state) => {
+          let object = Object::new(event.manifest, state);
+          self.reconciler.terminate(object);
+          if let Some(persisted) = &self.persisted {
+            if let Err(e) = persisted.remove(&name).await {
+              log::error!("{e}");
+            }
+          }
+        }
+        Err(e) => {
+          log::error!("{}", Error::controller(&name, e));
+          self.store.release(&name);
+        }
+      }
+    }
+
+    for manifest in self.store.list() {
+      let name = manifest.name().to_owned();
+      let res = match self.is_accepted(&manifest) {
+        true => self.create_object(manifest, Priority::Change).await,
+        false => self.apply(manifest, Priority::Change).await,
+      };
+      if let Err(e) = res {
+        self.fail(name, e).await;
+      }
+    }
+  }
+
+  /// Whether the current generation of an object was admitted and accepted,
+  /// e.g. by a previous controller of the kind.
+  fn is_accepted(&self, manifest: &ObjectManifest<O>) -> bool {
+    let Ok(status) = self.store.watch_status(manifest.name()) else {
+      return false;
+    };
+
+    let status = status.borrow();
+    status
+      .conditions
+      .get(Condition::ACCEPTED)
+      .is_some_and(|accepted| {
+        accepted.is_true()
+          && accepted.observed_generation == manifest.meta.generation
+      })
+  }
+
+  /// Registers the depth probes of the operator's channels and queue. This
+  /// waits for the start, so that the probes of a replaced operator are
+  /// unregistered by then, rather than reported twice.
+  fn register_probes(&mut self) {
+    let metrics = &self.context.metrics;
+    let labels = |channel: &str| {
+      vec![
+        ("channel", channel.to_owned()),
+        ("kind", O::kind().to_owned()),
+      ]
+    };
+
+    let requeue_rx = self.requeue_rx.clone();
+    let retry_rx = self.retry_rx.clone();
+    let trigger_rx = self.trigger_rx.clone();
+    self.probes = vec![
+      metrics.register_depth(labels("requeue"), move || requeue_rx.len()),
+      metrics.register_depth(labels("retry"), move || retry_rx.len()),
+      metrics.register_depth(labels("trigger"), move || trigger_rx.len()),
+      metrics.register_depth(labels("reconcile"), self.reconciler.depth()),
+    ];
+  }
+
+  /// Stops taking new work and waits for the reconciles in flight, leaving
+  /// objects to the next controller. Without one, pending events are
+  /// dropped and the statuses of removed objects released.
+  async fn drain(&mut self) {
+    let _activity = self.context.activity.enter();
+    log::debug!(kind = O::kind(), "drain operator");
+
+    for (_, event, _activity) in std::mem::take(&mut self.delayed) {
+      self.deliver(event).await;
+    }
+    self.reconciler.drain().await;
+    for probe in self.probes.drain(..) {
+      self.context.metrics.unregister_depth(probe);
+    }
+
+    if !self.store.is_managed() {
+      for event in self.store.events().drain() {
+        self.store.release(event.manifest.name());
+      }
+    }
+  }
+
+  /// Delivers a store event, unless faults delay it. Delayed events keep
+  /// their order, without holding back the other work of the operator.
+  async fn handle_store_event(&mut self, event: StoreEvent<O>) {
+    let delay = self.context.faults.event_delay();
+    if delay.is_none() && self.delayed.is_empty() {
+      return self.deliver(event).await;
+    }
+
+    let mut at = Instant::now() + delay.unwrap_or_default();
+    if let Some((last, ..)) = self.delayed.back() {
+      at = at.max(*last);
+    }
+    let activity = self.context.activity.enter();
+    self.delayed.push_back((at, event, activity));
+  }
+
+  async fn deliver(&mut self, event: StoreEvent<O>) {
+    let _activity = self.context.activity.enter();
+
+    let span = log::span!(
+      "store_event",
+      change = ?event.change,
+      kind = O::kind(),
+      name = %event.manifest.name(),
+    );
+
+    async {
+      log::debug!("received store event");
 
       if let Err(e) = self.handle_event(event).await {
-        eprintln!("{e}");
+        log::error!("{e}");
       }
     }
+    .instrument(span)
+    .await;
   }
 
-  async fn handle_event(&mut self, event: StoreEvent<O>) -> Result<()> {
-    let StoreEvent { change, manifest } = event;
+  /// Rebuilds every object from the store, as if the engine was restarted.
+  async fn restart(&mut self) {
+    let _activity = self.context.activity.enter();
+    log::debug!(kind = O::kind(), "restart operator");
+
+    let objects = std::mem::take(&mut *self.objects.write());
+    self.reconciler.reset().await;
+    for object in objects.iter() {
+      self.reconciler.forget(&object.id);
+    }
+
+    for manifest in self.store.list() {
+      let name = manifest.name().to_owned();
+      if let Err(e) = self.create_object(manifest, Priority::Resync).await {
+        self.fail(name, e).await;
+      }
+    }
+  }
+
+  /// Retries the admission or initialization of an object, from its latest
+  /// manifest, unless an update healed it or failed again meanwhile.
+  async fn handle_retry(&mut self, name: ObjectName, token: u64) {
+    let Some(failure) = self.failures.get_mut(&name) else {
+      return;
+    };
+    if failure.retry != Some(token) {
+      return;
+    }
+    failure.retry = None;
+
+    let _activity = self.context.activity.enter();
+    let Some(manifest) = self.store.get(&name) else {
+      self.failures.remove(&name);
+      return;
+    };
+
+    log::debug!(kind = O::kind(), name = %name, "retry");
+    match self.apply(manifest, Priority::Retry).await {
+      Ok(()) => {
+        self.failures.remove(&name);
+      }
+      Err(e) => self.fail(name, e).await,
+    }
+  }
+
+  /// Records a failed admission or initialization on the object's events.
+  /// A denied manifest waits for an update, other failures are retried with
+  /// backoff.
+  async fn fail(&mut self, name: ObjectName, failed: Failed) {
+    let Failed { reason, error: e } = failed;
+    let generation = self.store.get(&name).map(|m| m.meta.generation);
+    let denied = matches!(e, Error::AdmissionDenied { .. });
+
+    let failure = self.failures.entry(name.clone()).or_default();
+    if denied {
+      failure.retry = None;
+      if failure.denied == generation {
+        return;
+      }
+      failure.denied = generation;
+    } else {
+      failure.denied = None;
+      failure.attempts += 1;
+    }
+
+    let retry = match failure.retry {
+      None if !denied => {
+        self.retries += 1;
+        failure.retry = Some(self.retries);
+        Some((self.retries, retry_delay(failure.attempts)))
+      }
+      _ => None,
+    };
+
+    log::error!("{e}");
+    self
+      .context
+      .command
+      .recorder::<O>(name.clone())
+      .warning(reason, e.to_string())
+      .await
+      .ok();
+
+    if let Some(generation) = generation {
+      let rejected =
+        Condition::new(Condition::ACCEPTED, ConditionStatus::False)
+          .with_reason(reason)
+          .with_message(cause(&e))
+          .with_observed_generation(generation);
+      self.store.set_condition(&name, rejected).ok();
+    }
+
+    if let Some((token, delay)) = retry {
+      let retry_tx = self.retry_tx.clone();
+      tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        retry_tx.send((name, token)).ok();
+      });
+    }
+  }
+
+  fn handle_requeue(&mut self, id: ObjectId, priority: Priority) {
+    let object = self.objects.read().get_by_id(&id).cloned();
+    if let Some(object) = object {
+      self.reconciler.reconcile(object, priority);
+    }
+  }
+
+  /// Reconciles objects whose watched resources changed, once per object
+  /// however many changes are pending.
+  fn handle_trigger(&mut self, name: ObjectName) {
+    let _activity = self.context.activity.enter();
+
+    let names = std::iter::once(name)
+      .chain(self.trigger_rx.try_iter())
+      .collect::<BTreeSet<_>>();
+
+    for name in names {
+      log::debug!(kind = O::kind(), name = %name, "triggered");
+      let object = self.objects.read().get(&name).cloned();
+      if let Some(object) = object {
+        self.reconciler.reconcile(object, Priority::Change);
+      }
+    }
+  }
+
+  async fn create_object(
+    &mut self,
+    manifest: ObjectManifest<O>,
+    priority: Priority,
+  ) -> Result<(), Failed> {
     let name = manifest.name().to_owned();
 
-    match change {
-      Change::Create => {
-        println!("{}: admit manifest", name);
-        let manifest = self.controller.admit_manifest(manifest).await?;
-        self.store.patch(manifest.clone())?;
+    let state = match self.restore_state(&name).await {
+      Some(state) => state,
+      None => {
+        log::debug!("initialize state");
+        self
+          .controller
+          .initialize_state(&manifest)
+          .await
+          .map_err(|e| Error::controller(&name, e))
+          .map_err(failed("InitializeFailed"))?
+      }
+    };
+
+    if let Some(states) = &self.states {
+      states.publish(&name, &state);
+    }
+    let object = Object::new(manifest, state);
+    self.objects.write().insert(object.clone());
+
+    self.accept(object, priority).await
+  }
 
-        println!("{}: initialize state", name);
-        let state = self.controller.initialize_state(&manifest).await?;
+  /// Marks an admitted and initialized object as accepted, then reconciles
+  /// it, or marks its generation as reconciled if the controller has nothing
+  /// to do.
+  async fn accept(
+    &mut self,
+    object: Object<O>,
+    priority: Priority,
+  ) -> Result<(), Failed> {
+    let name = object.name().to_owned();
+    let generation = object.manifest.meta.generation;
+    let reconcile = self
+      .controller
+      .should_reconcile(&object.manifest)
+      .await
+      .map_err(|e| Error::controller(&name, e))
+      .map_err(failed("ShouldReconcileFailed"))?;
 
-        let object = Object::new(manifest.clone(), state);
-        self.objects.insert(object.clone());
+    let accepted = Condition::new(Condition::ACCEPTED, ConditionStatus::True)
+      .with_reason("Accepted")
+      .with_observed_generation(generation);
+    self.store.set_condition(&name, accepted).ok();
 
-        if self.controller.should_reconcile(&manifest).await? {
-          self.reconciler.reconcile(object);
-        }
+    match reconcile {
+      true => self.reconciler.reconcile(object, priority),
+      false => self.store.set_reconciled(&name, generation),
+    }
+
+    Ok(())
+  }
+
+  /// Returns the checkpointed state of an object, if it's persisted. A
+  /// checkpoint that can't be restored is ignored, so the state gets
+  /// initialized again.
+  async fn restore_state(&self, name: &ObjectName) -> Option<O::State> {
+    let persisted = self.persisted.as_ref()?;
+
+    match persisted.restore(name).await {
+      Ok(Some(state)) => {
+        log::debug!("restore state");
+        Some(state)
+      }
+      Ok(None) => None,
+      Err(e) => {
+        log::error!("{e}");
+        None
       }
-      Change::Update => {
-        if let Some(object) = self.objects.patch_manifest(manifest.clone()) {
-          println!("{}: admit manifest", name);
-          let manifest = self.controller.admit_manifest(manifest).await?;
-          self.store.patch(manifest.clone())?;
+    }
+  }
+
+  async fn admit(
+    &self,
+    manifest: ObjectManifest<O>,
+  ) -> Result<ObjectManifest<O>, Failed> {
+    let name = manifest.name().to_owned();
+    let span = log::span!("admission", kind = O::kind(), name = %name);
 
-          if self.controller.should_reconcile(&manifest).await? {
-            self.reconciler.reconcile(object);
+    async {
+      log::debug!("admit manifest");
+      let manifest = self
+        .controller
+        .admit_manifest(manifest)
+        .await
+        .map_err(|e| Error::admission_denied(&name, e))
+        .map_err(failed("AdmissionDenied"))?;
+      self
+        .store
+        .patch(manifest.clone())
+        .map_err(failed("AdmissionFailed"))?;
+
+      Ok(manifest)
+    }
+    .instrument(span)
+    .await
+  }
+
+  async fn handle_event(&mut self, event: StoreEvent<O>) -> Result<()> {
+    let StoreEvent { change, manifest } = event;
+    let name = manifest.name().to_owned();
+
+    match change {
+      Change::Create | Change::Update => {
+        match self.apply(manifest, Priority::Change).await {
+          Ok(()) => {
+            self.failures.remove(&name);
           }
-        } else {
-          bail!("no object found for name '{}'", name)
+          Err(e) => self.fail(name, e).await,
         }
       }
       Change::Delete => {
-        if self.objects.remove(&name).is_some() {
-          println!("{}: terminate", name);
-          self.controller.terminate(&manifest).await?;
-
-          self.objects.remove(&name);
-        } else {
-          bail!("no object found for name '{}'", name)
+        let object = self.objects.write().remove(&name);
+        let failed = self.failures.remove(&name).is_some();
+        match object {
+          Some(mut object) => {
+            object.manifest = manifest;
+            self.reconciler.terminate(object);
+            if let Some(states) = &self.states {
+              states.remove(&name);
+            }
+            if let Some(persisted) = &self.persisted {
+              persisted.remove(&name).await?;
+            }
+          }
+          None => {
+            self.store.release(&name);
+            if !failed {
+              return Err(Error::NotFound {
+                kind: O::kind(),
+                name,
+              });
+            }
+          }
         }
       }
     }
 
     Ok(())
   }
+
+  /// Admits a manifest, then reconciles its object, creating it first if
+  /// it's new or its creation failed before.
+  async fn apply(
+    &mut self,
+    manifest: ObjectManifest<O>,
+    priority: Priority,
+  ) -> Result<(), Failed> {
+    let manifest = self.admit(manifest).await?;
+
+    let object = self.objects.write().patch_manifest(manifest.clone());
+    match object {
+      Some(object) => self.accept(object, priority).await,
+      None => self.create_object(manifest, priority).await,
+    }
+  }
+}
+
+/// Describes the cause of an error, without the object it's about.
+fn cause(e: &Error) -> String {
+  match e {
+    Error::AdmissionDenied { source, .. }
+    | Error::Controller { source, .. } => source.to_string(),
+    e => e.to_string(),
+  }
 }
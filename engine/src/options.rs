@@ -0,0 +1,235 @@
+#[cfg(feature = "serde")]
+use std::sync::Arc;
+use std::{marker::PhantomData, time::Duration};
+
+use flume::Sender;
+use gusto_core::{
+  ObjectDefinition, ObjectKind, ObjectManifest, ObjectName, Result
+};
+
+#[cfg(feature = "serde")]
+use gusto_core::PersistentState;
+
+#[cfg(feature = "serde")]
+use crate::Checkpoints;
+use crate::{
+  state::PersistedStates, Engine, IndexFn, RateLimit, SnapshotFn, WatcherId
+};
+
+/// Requeues shorter than this count towards hot-loop detection by default.
+const HOT_LOOP_THRESHOLD: Duration = Duration::from_millis(100);
+/// Time after which queued reconciles gain a priority level by default.
+const QUEUE_AGING: Duration = Duration::from_secs(5);
+
+type WatchFn = Box<
+  dyn FnOnce(&Engine, Sender<ObjectName>) -> Result<(ObjectKind, WatcherId)>
+    + Send,
+>;
+
+/// ControllerOptions
+pub struct ControllerOptions<O>
+where
+  O: ObjectDefinition,
+{
+  pub(crate) watches: Vec<WatchFn>,
+  pub(crate) reconcile: ReconcileOptions,
+  pub(crate) share_state: Option<SnapshotFn<O>>,
+  pub(crate) persist_state: Option<PersistedStates<O>>,
+  _object: PhantomData<O>,
+}
+
+/// ReconcileOptions
+#[derive(Clone, Debug)]
+pub struct ReconcileOptions {
+  pub(crate) max_concurrent: Option<usize>,
+  pub(crate) rate_limit: Option<RateLimit>,
+  pub(crate) object_rate_limit: Option<RateLimit>,
+  pub(crate) hot_loop_threshold: Option<Duration>,
+  pub(crate) queue_aging: Duration,
+  pub(crate) timeout: Option<Duration>,
+}
+
+impl Default for ReconcileOptions {
+  fn default() -> Self {
+    Self {
+      max_concurrent: None,
+      rate_limit: None,
+      object_rate_limit: None,
+      hot_loop_threshold: Some(HOT_LOOP_THRESHOLD),
+      queue_aging: QUEUE_AGING,
+      timeout: None,
+    }
+  }
+}
+
+impl<O> ControllerOptions<O>
+where
+  O: ObjectDefinition,
+{
+  pub fn new() -> Self {
+    Self {
+      watches: Vec::new(),
+      reconcile: Default::default(),
+      share_state: None,
+      persist_state: None,
+      _object: PhantomData,
+    }
+  }
+
+  /// Publishes a copy of each object's state once it's initialized and
+  /// after each reconcile, for [`gusto_core::Command::inspect_state`] and
+  /// [`gusto_core::Command::watch_state`].
+  pub fn share_state(mut self) -> Self
+  where
+    O::State: Clone,
+  {
+    self.share_state = Some(O::State::clone);
+    self
+  }
+
+  /// Checkpoints each object's state after each successful reconcile, and
+  /// restores it instead of calling
+  /// [`gusto_core::Controller::initialize_state`] when the object is created
+  /// again, e.g. after a restart. Checkpoints of deleted objects are removed.
+  #[cfg(feature = "serde")]
+  pub fn persist_state(mut self, checkpoints: Arc<dyn Checkpoints>) -> Self
+  where
+    O::State: PersistentState,
+  {
+    self.persist_state = Some(PersistedStates::new(checkpoints));
+    self
+  }
+
+  /// Limits how many objects are reconciled at once. Other objects wait
+  /// their turn in FIFO order. Unlimited by default.
+  pub fn max_concurrent_reconciles(mut self, max: usize) -> Self {
+    self.reconcile.max_concurrent = Some(max);
+    self
+  }
+
+  /// Aborts reconciles running longer than the deadline, and retries them
+  /// with a backoff. The cancellation token of the reconcile fires first.
+  pub fn reconcile_timeout(mut self, timeout: Duration) -> Self {
+    self.reconcile.timeout = Some(timeout);
+    self
+  }
+
+  /// Sets how long a queued reconcile waits before gaining a priority
+  /// level, so retries and resyncs aren't starved by changes. Zero disables
+  /// aging.
+  pub fn queue_aging(mut self, aging: Duration) -> Self {
+    self.reconcile.queue_aging = aging;
+    self
+  }
+
+  /// Limits the rate of reconciles across all objects of the controller.
+  pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+    self.reconcile.rate_limit = Some(limit);
+    self
+  }
+
+  /// Limits the rate of reconciles of each object.
+  pub fn object_rate_limit(mut self, limit: RateLimit) -> Self {
+    self.reconcile.object_rate_limit = Some(limit);
+    self
+  }
+
+  /// Throttles objects that keep requeueing sooner than the threshold, with
+  /// an exponential backoff. `None` disables the protection.
+  pub fn hot_loop_threshold(mut self, threshold: Option<Duration>) -> Self {
+    self.reconcile.hot_loop_threshold = threshold;
+    self
+  }
+
+  /// Reconciles the owner of an object of the given kind whenever that
+  /// object changes, if the owner is of the controlled kind.
+  pub fn owns<K>(mut self) -> Self
+  where
+    K: ObjectDefinition,
+  {
+    self.watches.push(Box::new(|engine, trigger_tx| {
+      let owners = engine.owners();
+      let id = engine.store::<K>()?.watch(move |event| {
+        let owner = owners
+          .read()
+          .owner_of(K::kind(), event.manifest.name())
+          .cloned();
+        if let Some(owner) = owner.filter(|owner| owner.kind == O::kind()) {
+          trigger_tx.send(owner.name).ok();
+        }
+      });
+
+      Ok((K::kind(), id))
+    }));
+    self
+  }
+
+  /// Reconciles the objects returned by the mapper whenever an object of the
+  /// given kind changes.
+  pub fn watches<K>(
+    mut self,
+    mapper: impl Fn(&ObjectManifest<K>) -> Vec<ObjectName> + Send + Sync + 'static,
+  ) -> Self
+  where
+    K: ObjectDefinition,
+  {
+    self.watches.push(Box::new(|engine, trigger_tx| {
+      let id = engine.store::<K>()?.watch(move |event| {
+        for name in mapper(&event.manifest) {
+          trigger_tx.send(name).ok();
+        }
+      });
+
+      Ok((K::kind(), id))
+    }));
+    self
+  }
+}
+
+impl<O> Default for ControllerOptions<O>
+where
+  O: ObjectDefinition,
+{
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// ObjectOptions
+pub struct ObjectOptions<O>
+where
+  O: ObjectDefinition,
+{
+  pub(crate) indexes: Vec<(&'static str, IndexFn<O>)>,
+}
+
+impl<O> ObjectOptions<O>
+where
+  O: ObjectDefinition,
+{
+  pub fn new() -> Self {
+    Self {
+      indexes: Vec::new(),
+    }
+  }
+
+  /// Indexes objects by the keys the function returns, for lookups through
+  /// [`gusto_core::Lister::by_index`].
+  pub fn index(
+    mut self,
+    name: &'static str,
+    func: impl Fn(&ObjectManifest<O>) -> Vec<String> + Send + Sync + 'static,
+  ) -> Self {
+    self.indexes.push((name, Box::new(func)));
+    self
+  }
+}
+
+impl<O> Default for ObjectOptions<O>
+where
+  O: ObjectDefinition,
+{
+  fn default() -> Self {
+    Self::new()
+  }
+}
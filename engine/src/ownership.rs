@@ -1,53 +1,108 @@
-use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet};
 
-use anyhow::{bail, Result};
-use gusto_core::{ObjectKind, ObjectName};
+use gusto_core::{Error, ObjectKind, ObjectName, ObjectRef, Result};
 
 /// Owned
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Owned {
   pub kind: ObjectKind,
   pub name: ObjectName,
 }
 
 /// Owners
-#[derive(Default)]
+///
+/// Objects have at most one owner, indexed both ways.
+#[derive(Clone, Default)]
 pub struct Owners {
-  inner: BTreeMap<ObjectName, BTreeSet<Owned>>,
+  inner: BTreeMap<ObjectRef, BTreeSet<Owned>>,
+  owner_of: BTreeMap<Owned, ObjectRef>,
 }
 
 impl Owners {
+  /// Records that the owner owns the given object, which it may already do.
   pub fn own(
     &mut self,
-    owner: ObjectName,
+    owner: ObjectRef,
     owned_kind: ObjectKind,
     owned_name: ObjectName,
   ) -> Result<()> {
+    self.check(&owner, owned_kind, &owned_name)?;
+
     let owned = Owned {
       kind: owned_kind,
       name: owned_name,
     };
+    self
+      .inner
+      .entry(owner.clone())
+      .or_default()
+      .insert(owned.clone());
+    self.owner_of.insert(owned, owner);
 
-    match self.inner.entry(owner) {
-      Entry::Vacant(entry) => {
-        entry.insert(BTreeSet::from([owned]));
-      }
-      Entry::Occupied(mut entry) => {
-        if !entry.get().contains(&owned) {
-          entry.get_mut().insert(owned);
-        } else {
-          bail!("{} is already owned", owned.name);
-        }
+    Ok(())
+  }
+
+  /// Checks that the owner can own the given object, i.e. that no other
+  /// object owns it.
+  pub fn check(
+    &self,
+    owner: &ObjectRef,
+    owned_kind: ObjectKind,
+    owned_name: &ObjectName,
+  ) -> Result<()> {
+    match self.owner_of(owned_kind, owned_name) {
+      Some(current) if current != owner => {
+        Err(Error::AlreadyOwned {
+          owner: current.name.to_owned(),
+          name: owned_name.to_owned(),
+        })
       }
-    };
+      _ => Ok(()),
+    }
+  }
 
-    Ok(())
+  pub fn owned(&self, owner: &ObjectRef) -> Option<&BTreeSet<Owned>> {
+    self.inner.get(owner)
   }
 
-  pub fn remove_owner(
-    &mut self,
-    owner: &ObjectName,
-  ) -> Option<BTreeSet<Owned>> {
-    self.inner.remove(owner)
+  pub fn iter(
+    &self,
+  ) -> impl Iterator<Item = (&ObjectRef, &BTreeSet<Owned>)> + '_ {
+    self.inner.iter()
+  }
+
+  pub fn owner_of(
+    &self,
+    kind: ObjectKind,
+    name: &ObjectName,
+  ) -> Option<&ObjectRef> {
+    self.owner_of.get(&Owned {
+      kind,
+      name: name.to_owned(),
+    })
+  }
+
+  /// Forgets a removed object, both as an owner and as owned.
+  pub fn release(&mut self, kind: ObjectKind, name: &ObjectName) {
+    let owned = Owned {
+      kind,
+      name: name.to_owned(),
+    };
+    if let Some(owner) = self.owner_of.remove(&owned) {
+      if let Some(siblings) = self.inner.get_mut(&owner) {
+        siblings.remove(&owned);
+        if siblings.is_empty() {
+          self.inner.remove(&owner);
+        }
+      }
+    }
+
+    let owner = ObjectRef {
+      kind,
+      name: name.to_owned(),
+    };
+    for owned in self.inner.remove(&owner).into_iter().flatten() {
+      self.owner_of.remove(&owned);
+    }
   }
 }
@@ -0,0 +1,116 @@
+use std::{
+  collections::{HashMap, VecDeque}, time::Duration
+};
+
+use tokio::time::Instant;
+
+use crate::ObjectId;
+
+const LEVELS: usize = 3;
+
+/// Priority
+///
+/// Urgency of a reconcile, from lowest to highest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+  /// Retry after a failed reconcile.
+  Retry,
+  /// Requeue requested by the controller, or rebuild after a restart.
+  Resync,
+  /// Change to the object or to an object it watches.
+  Change,
+}
+
+/// Queued
+struct Queued {
+  id: ObjectId,
+  since: Instant,
+}
+
+/// WorkQueue
+///
+/// Objects waiting for a reconcile slot. Higher priorities are served first,
+/// FIFO within a priority, and waiting objects gain a priority level every
+/// `aging` so low-priority work still runs.
+pub(crate) struct WorkQueue {
+  levels: [VecDeque<Queued>; LEVELS],
+  queued: HashMap<ObjectId, Priority>,
+  limit: usize,
+  aging: Duration,
+}
+
+impl WorkQueue {
+  pub fn new(limit: Option<usize>, aging: Duration) -> Self {
+    Self {
+      levels: Default::default(),
+      queued: Default::default(),
+      limit: limit.unwrap_or(usize::MAX).max(1),
+      aging,
+    }
+  }
+
+  /// Queues an object, or raises its priority if it's already queued lower.
+  pub fn push(&mut self, id: ObjectId, priority: Priority) {
+    let mut since = Instant::now();
+
+    if let Some(queued) = self.queued.get(&id).copied() {
+      if queued >= priority {
+        return;
+      }
+
+      let level = &mut self.levels[queued as usize];
+      if let Some(pos) = level.iter().position(|q| q.id == id) {
+        if let Some(prev) = level.remove(pos) {
+          since = prev.since;
+        }
+      }
+    }
+
+    self.queued.insert(id, priority);
+    self.levels[priority as usize].push_back(Queued { id, since });
+  }
+
+  pub fn remove(&mut self, id: &ObjectId) {
+    if let Some(priority) = self.queued.remove(id) {
+      self.levels[priority as usize].retain(|queued| &queued.id != id);
+    }
+  }
+
+  /// Takes the object with the highest aged priority, the longest waiting
+  /// one on ties.
+  pub fn pop(&mut self) -> Option<ObjectId> {
+    let now = Instant::now();
+
+    let (level, _) = self
+      .levels
+      .iter()
+      .enumerate()
+      .filter_map(|(level, queue)| {
+        let front = queue.front()?;
+        let waited = now.saturating_duration_since(front.since);
+        let aged = match self.aging.is_zero() {
+          true => 0,
+          false => (waited.as_nanos() / self.aging.as_nanos()) as usize,
+        };
+        Some((level, (level.saturating_add(aged), waited)))
+      })
+      .max_by_key(|(_, rank)| *rank)?;
+
+    let queued = self.levels[level].pop_front()?;
+    self.queued.remove(&queued.id);
+    Some(queued.id)
+  }
+
+  pub fn priority(&self, id: &ObjectId) -> Option<Priority> {
+    self.queued.get(id).copied()
+  }
+
+  pub fn len(&self) -> usize {
+    self.queued.len()
+  }
+
+  /// Returns how many reconciliations may run at once.
+  pub fn limit(&self) -> usize {
+    self.limit
+  }
+}
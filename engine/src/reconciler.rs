@@ -1,9 +1,54 @@
-use std::{collections::HashSet, marker::PhantomData, sync::Arc};
+use std::{
+  collections::{HashMap, HashSet}, sync::Arc, time::{Duration, Instant}
+};
 
-use gusto_core::{Command, Controller, ObjectDefinition};
-use parking_lot::RwLock;
+use flume::Sender;
+use gusto_core::{
+  CancellationToken, Command, Controller, Error, ObjectDefinition
+};
+use parking_lot::{Mutex, RwLock};
+use tokio::sync::Notify;
 
-use crate::{Object, ObjectId};
+use crate::{
+  limiter::Limits, log::{self, Instrument}, queue::WorkQueue, state::{PersistedStates, SharedStates}, Activity, Metrics, Object, ObjectId, OperatorContext, Priority, ReconcileOptions, ReconcileState, Store
+};
+
+/// Failed reconciles are retried, since controllers are expected to converge
+/// and errors are mostly transient, with a backoff between these bounds.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(300);
+/// Attempts after which a termination that keeps requeueing or failing is
+/// given up on, releasing the object anyway.
+const TERMINATE_MAX_ATTEMPTS: u32 = 20;
+
+/// Reconciliations in progress, either waiting for a slot or running, with
+/// the object version to reconcile next.
+type Pending<O> = HashMap<ObjectId, Option<Next<O>>>;
+
+/// Next
+struct Next<O>
+where
+  O: ObjectDefinition,
+{
+  object: Object<O>,
+  /// Highest priority requested since the object was queued.
+  priority: Priority,
+}
+
+/// Requeue
+struct Requeue {
+  after: Duration,
+  priority: Priority,
+}
+
+/// Running
+struct Running {
+  generation: u64,
+  cancellation: CancellationToken,
+}
+
+/// Scheduled requeues, with the instant they fire at.
+type Scheduled = HashMap<ObjectId, tokio::time::Instant>;
 
 /// Reconciler
 pub struct Reconciler<C, O>
@@ -11,10 +56,33 @@ where
   C: Controller<O>,
   O: ObjectDefinition,
 {
-  pending: Arc<RwLock<HashSet<ObjectId>>>,
-  command: Command,
-  controller: Arc<C>,
-  o: PhantomData<O>,
+  shared: Arc<Shared<C, O>>,
+}
+
+/// State shared with the spawned reconciliations.
+struct Shared<C, O>
+where
+  C: Controller<O>,
+  O: ObjectDefinition,
+{
+  work: Arc<Mutex<Work<O>>>,
+  scheduled: Arc<Mutex<Scheduled>>,
+  worker: Worker<C, O>,
+  activity: Activity,
+  requeue_tx: Sender<(ObjectId, Priority)>,
+  /// Notified whenever a reconciliation finishes.
+  finished: Notify,
+}
+
+/// Work
+struct Work<O>
+where
+  O: ObjectDefinition,
+{
+  pending: Pending<O>,
+  queue: WorkQueue,
+  running: HashMap<ObjectId, Running>,
+  terminating: HashSet<ObjectId>,
 }
 
 impl<C, O> Reconciler<C, O>
@@ -22,36 +90,580 @@ where
   C: Controller<O>,
   O: ObjectDefinition,
 {
-  pub fn new(command: Command, controller: Arc<C>) -> Self {
-    Self {
+  pub(crate) fn new(
+    controller: Arc<C>,
+    store: Arc<Store<O>>,
+    context: &OperatorContext,
+    options: ReconcileOptions,
+    states: Option<Arc<SharedStates<O>>>,
+    persisted: Option<Arc<PersistedStates<O>>>,
+    requeue_tx: Sender<(ObjectId, Priority)>,
+  ) -> Self {
+    let work = Arc::new(Mutex::new(Work {
       pending: Default::default(),
-      command,
-      controller,
-      o: PhantomData,
+      queue: WorkQueue::new(options.max_concurrent, options.queue_aging),
+      running: Default::default(),
+      terminating: Default::default(),
+    }));
+
+    Self {
+      shared: Arc::new(Shared {
+        work,
+        scheduled: Default::default(),
+        worker: Worker {
+          attempts: Default::default(),
+          command: context.command.clone(),
+          controller,
+          store,
+          metrics: context.metrics.clone(),
+          timeout: options.timeout,
+          states,
+          persisted,
+          limits: Limits::new(
+            context.limiter.clone(),
+            options.rate_limit,
+            options.object_rate_limit,
+            options.hot_loop_threshold,
+          ),
+        },
+        activity: context.activity.clone(),
+        requeue_tx,
+        finished: Notify::new(),
+      }),
     }
   }
 
-  pub fn reconcile(&mut self, object: Object<O>) {
-    if self.pending.read().contains(&object.id) {
-      println!("skip reconciliation");
+  /// Returns a probe of the reconciliations waiting for a slot, which count
+  /// as pending work.
+  pub fn depth(&self) -> impl Fn() -> usize + Send + Sync + 'static {
+    let work = self.shared.work.clone();
+    move || work.lock().queue.len()
+  }
+
+  pub fn reconcile(&mut self, object: Object<O>, priority: Priority) {
+    let mut work = self.shared.work.lock();
+    let id = object.id;
+
+    if let Some(queued) = work.pending.get_mut(&id) {
+      log::debug!(
+        kind = O::kind(),
+        name = %object.name(),
+        ?priority,
+        "queue reconciliation"
+      );
+      let priority = queued
+        .as_ref()
+        .map_or(priority, |next| next.priority.max(priority));
+      let generation = object.manifest.meta.generation;
+      *queued = Some(Next { object, priority });
+
+      match work.running.get(&id) {
+        Some(running) if running.generation < generation => {
+          log::debug!(kind = O::kind(), "cancel superseded reconciliation");
+          running.cancellation.cancel();
+        }
+        Some(_) => {}
+        None => {
+          work.queue.push(id, priority);
+          self.shared.dispatch(&mut work);
+        }
+      }
       return;
     }
 
-    let pending = self.pending.clone();
-    let command = self.command.clone();
-    let controller = self.controller.clone();
+    work.pending.insert(id, Some(Next { object, priority }));
+    work.queue.push(id, priority);
+    self.shared.dispatch(&mut work);
+  }
+
+  /// Drops the queued reconciliations and returns once the ones in flight
+  /// and the terminations have finished, before handing the objects over to
+  /// another controller.
+  pub async fn drain(&mut self) {
+    self.settle(false).await;
+
+    loop {
+      let finished = self.shared.finished.notified();
+      if self.shared.work.lock().terminating.is_empty() {
+        break;
+      }
+      finished.await;
+    }
+  }
+
+  /// Drops the queued reconciliations and cancels the ones in flight,
+  /// returning once they have stopped, before objects are rebuilt.
+  pub async fn reset(&mut self) {
+    self.settle(true).await;
+  }
+
+  async fn settle(&mut self, cancel: bool) {
+    {
+      let mut work = self.shared.work.lock();
+      let Work {
+        pending,
+        queue,
+        running,
+        ..
+      } = &mut *work;
+      pending.retain(|id, next| {
+        queue.remove(id);
+        *next = None;
+        running.contains_key(id)
+      });
+      if cancel {
+        for running in running.values() {
+          running.cancellation.cancel();
+        }
+      }
+    }
+
+    loop {
+      let finished = self.shared.finished.notified();
+      if self.shared.work.lock().running.is_empty() {
+        break;
+      }
+      finished.await;
+    }
+  }
 
+  /// Cancels the reconciliation of a deleted object and drops the queued
+  /// ones. Then, in the background once the one in flight has stopped,
+  /// terminates the object until the controller neither requeues nor fails,
+  /// or it ran out of attempts.
+  pub fn terminate(&mut self, object: Object<O>) {
+    {
+      let mut work = self.shared.work.lock();
+      work.terminating.insert(object.id);
+      if let Some(running) = work.running.get(&object.id) {
+        running.cancellation.cancel();
+        work.pending.insert(object.id, None);
+      } else if work.pending.remove(&object.id).is_some() {
+        work.queue.remove(&object.id);
+      }
+    }
+
+    let shared = self.shared.clone();
+    let activity = shared.activity.enter();
     tokio::spawn(async move {
+      shared.stopped(&object.id).await;
+      shared.forget(&object.id);
+      drop(activity);
+
+      let mut attempt = 0;
+      loop {
+        attempt += 1;
+        let activity = shared.activity.enter();
+        let requeue = shared.worker.terminate(&object, attempt).await;
+        drop(activity);
+
+        match requeue {
+          Some(_) if attempt == TERMINATE_MAX_ATTEMPTS => {
+            shared.worker.abandon(&object, attempt).await;
+            break;
+          }
+          Some(delay) => tokio::time::sleep(delay).await,
+          None => break,
+        }
+      }
+
+      shared.worker.store.release(object.name());
+      shared.work.lock().terminating.remove(&object.id);
+      shared.finished.notify_waiters();
+    });
+  }
+
+  pub fn forget(&mut self, id: &ObjectId) {
+    self.shared.forget(id);
+  }
+
+  pub(crate) fn tracker(&self) -> Tracker<O> {
+    Tracker {
+      work: self.shared.work.clone(),
+      scheduled: self.shared.scheduled.clone(),
+    }
+  }
+}
+
+impl<C, O> Shared<C, O>
+where
+  C: Controller<O>,
+  O: ObjectDefinition,
+{
+  /// Starts queued reconciliations, by priority, while slots are available.
+  /// Rate-limited objects don't take a slot, they're requeued once their
+  /// tokens are available instead.
+  fn dispatch(self: &Arc<Self>, work: &mut Work<O>) {
+    while work.running.len() < work.queue.limit() {
+      let Some(id) = work.queue.pop() else {
+        break;
+      };
+      let Some(Some(next)) = work.pending.get(&id) else {
+        continue;
+      };
+      if let Err(wait) = self.worker.limits.try_reserve(id) {
+        log::debug!(
+          kind = O::kind(),
+          name = %next.object.name(),
+          ?wait,
+          "rate limited"
+        );
+        self.worker.metrics.rate_limited(O::kind());
+
+        let requeue = Requeue {
+          after: wait,
+          priority: next.priority,
+        };
+        let shared = self.clone();
+        tokio::spawn(async move { shared.schedule(id, requeue).await });
+        continue;
+      }
+
+      let Some(Next { object, .. }) =
+        work.pending.get_mut(&id).and_then(Option::take)
+      else {
+        continue;
+      };
+
+      let cancellation = CancellationToken::new();
+      work.running.insert(
+        id,
+        Running {
+          generation: object.manifest.meta.generation,
+          cancellation: cancellation.clone(),
+        },
+      );
+      let activity = self.activity.enter();
+
+      let shared = self.clone();
+      tokio::spawn(async move {
+        let requeue = shared.worker.run(&object, cancellation).await;
+        let requeued = shared.finish(object.id);
+        drop(activity);
+
+        if let (false, Some(requeue)) = (requeued, requeue) {
+          shared.schedule(object.id, requeue).await;
+        }
+      });
+    }
+  }
+
+  /// Returns once the reconciliation of an object, if any, has finished.
+  async fn stopped(&self, id: &ObjectId) {
+    loop {
+      let finished = self.finished.notified();
+      if !self.work.lock().running.contains_key(id) {
+        return;
+      }
+      finished.await;
+    }
+  }
+
+  fn forget(&self, id: &ObjectId) {
+    self.worker.attempts.write().remove(id);
+    self.worker.limits.forget(id);
+    self.scheduled.lock().remove(id);
+  }
+
+  /// Frees the slot of a finished reconciliation, queueing the object again
+  /// if a newer version came in meanwhile. Returns whether it did.
+  fn finish(self: &Arc<Self>, id: ObjectId) -> bool {
+    let mut work = self.work.lock();
+    work.running.remove(&id);
+
+    let next = work.pending.get(&id).and_then(Option::as_ref);
+    let requeued = match next.map(|next| next.priority) {
+      Some(priority) => {
+        work.queue.push(id, priority);
+        true
+      }
+      None => {
+        work.pending.remove(&id);
+        false
+      }
+    };
+
+    self.dispatch(&mut work);
+    self.finished.notify_waiters();
+    requeued
+  }
+
+  async fn schedule(&self, id: ObjectId, requeue: Requeue) {
+    let deadline = tokio::time::Instant::now() + requeue.after;
+    self.scheduled.lock().insert(id, deadline);
+    tokio::time::sleep_until(deadline).await;
+    self.scheduled.lock().remove(&id);
+    self.requeue_tx.send((id, requeue.priority)).ok();
+  }
+}
+
+/// Tracker
+///
+/// Shared view of the reconciliations in flight and scheduled.
+pub(crate) struct Tracker<O>
+where
+  O: ObjectDefinition,
+{
+  work: Arc<Mutex<Work<O>>>,
+  scheduled: Arc<Mutex<Scheduled>>,
+}
+
+impl<O> Tracker<O>
+where
+  O: ObjectDefinition,
+{
+  pub fn state(&self, id: &ObjectId) -> ReconcileState {
+    {
+      let work = self.work.lock();
+      if let Some(queued) = work.pending.get(id) {
+        return match work.running.contains_key(id) {
+          true => {
+            ReconcileState::Reconciling {
+              queued: queued.is_some(),
+            }
+          }
+          false => {
+            ReconcileState::Waiting {
+              priority: work.queue.priority(id).unwrap_or(Priority::Change),
+            }
+          }
+        };
+      }
+    }
+
+    match self.scheduled.lock().get(id) {
+      Some(deadline) => {
+        ReconcileState::Scheduled {
+          after: deadline
+            .saturating_duration_since(tokio::time::Instant::now()),
+        }
+      }
+      None => ReconcileState::Idle,
+    }
+  }
+}
+
+/// Worker
+struct Worker<C, O>
+where
+  C: Controller<O>,
+  O: ObjectDefinition,
+{
+  attempts: RwLock<HashMap<ObjectId, u32>>,
+  command: Command,
+  controller: Arc<C>,
+  store: Arc<Store<O>>,
+  metrics: Metrics,
+  timeout: Option<Duration>,
+  states: Option<Arc<SharedStates<O>>>,
+  persisted: Option<Arc<PersistedStates<O>>>,
+  limits: Limits,
+}
+
+impl<C, O> Worker<C, O>
+where
+  C: Controller<O>,
+  O: ObjectDefinition,
+{
+  /// Runs a single reconciliation, returning when it should be requeued.
+  async fn run(
+    &self,
+    object: &Object<O>,
+    cancellation: CancellationToken,
+  ) -> Option<Requeue> {
+    let attempt = *self.attempts.write().entry(object.id).or_default() + 1;
+    let span = log::span!(
+      "reconcile",
+      kind = O::kind(),
+      name = %object.name(),
+      object_id = %object.id,
+      attempt,
+    );
+
+    async {
+      let manifest = &object.manifest;
+      // Don't start reconciles that were cancelled while waiting, e.g. for
+      // the state held by the previous reconcile.
+      let mut state = tokio::select! {
+        biased;
+        () = cancellation.cancelled() => {
+          log::debug!("reconcile cancelled before starting");
+          return None;
+        }
+        state = object.state.write() => state,
+      };
+      let state = &mut *state;
+
+      log::debug!("reconcile");
+      self.metrics.reconcile_started(O::kind());
+      let started_at = Instant::now();
+      let command = self
+        .command
+        .clone()
+        .with_object(object.object_ref())
+        .with_cancellation(cancellation.clone());
+      let reconcile = self.controller.reconcile(manifest, state, &command);
+      let (res, timed_out) = match self.timeout {
+        Some(timeout) => {
+          match tokio::time::timeout(timeout, reconcile).await {
+            Ok(res) => (res, false),
+            Err(_) => {
+              cancellation.cancel();
+              (Err(Error::Timeout(timeout).into()), true)
+            }
+          }
+        }
+        None => (reconcile.await, false),
+      };
+      self.metrics.reconcile_finished(
+        O::kind(),
+        started_at.elapsed(),
+        res.is_ok(),
+        matches!(res, Ok(Some(_))),
+      );
+
+      // Reconciles stopped on request don't fail, timed out ones are retried.
+      let cancelled = cancellation.is_cancelled() && !timed_out;
+
+      // Nor do they share or save their state, which a deletion may have
+      // dropped already. A newer reconcile follows otherwise.
+      if let (false, Some(states)) = (cancelled, &self.states) {
+        states.publish(manifest.name(), state);
+      }
+
+      match res {
+        Ok(requeue) => {
+          self.attempts.write().remove(&object.id);
+          if let (false, Some(persisted)) = (cancelled, &self.persisted) {
+            if let Err(e) = persisted.save(manifest.name(), state).await {
+              log::error!("{e}");
+            }
+          }
+          self
+            .store
+            .set_reconciled(manifest.name(), manifest.meta.generation);
+
+          match requeue {
+            Some(requeue) => {
+              Some(Requeue {
+                after: self.throttle(object, requeue),
+                priority: Priority::Resync,
+              })
+            }
+            None => {
+              self.limits.cool_down(&object.id);
+              None
+            }
+          }
+        }
+        Err(_) if cancelled => {
+          log::debug!("reconcile cancelled");
+          None
+        }
+        Err(e) => {
+          self.attempts.write().insert(object.id, attempt);
+
+          self
+            .command
+            .recorder::<O>(manifest.name().to_owned())
+            .warning("ReconcileFailed", e.to_string())
+            .await
+            .ok();
+
+          let e = Error::controller(manifest.name(), e);
+          self.controller.reconcile_error(e).await;
+
+          Some(Requeue {
+            after: retry_delay(attempt),
+            priority: Priority::Retry,
+          })
+        }
+      }
+    }
+    .instrument(span)
+    .await
+  }
+}
+
+impl<C, O> Worker<C, O>
+where
+  C: Controller<O>,
+  O: ObjectDefinition,
+{
+  /// Runs a single termination, returning when it should run again.
+  async fn terminate(
+    &self,
+    object: &Object<O>,
+    attempt: u32,
+  ) -> Option<Duration> {
+    let span = log::span!(
+      "terminate",
+      kind = O::kind(),
+      name = %object.name(),
+      object_id = %object.id,
+      attempt,
+    );
+
+    async {
       let manifest = &object.manifest;
       let state = &mut object.state.write().await;
 
-      if let Err(e) = controller.reconcile(manifest, state, &command).await {
-        controller.reconcile_error(e).await;
+      log::debug!("terminate");
+      let command = self.command.clone().with_object(object.object_ref());
+      match self.controller.terminate(manifest, state, &command).await {
+        Ok(requeue) => requeue,
+        Err(e) => {
+          self
+            .command
+            .recorder::<O>(manifest.name().to_owned())
+            .warning("TerminateFailed", e.to_string())
+            .await
+            .ok();
+
+          let e = Error::controller(manifest.name(), e);
+          log::error!("{e}");
+
+          Some(retry_delay(attempt))
+        }
       }
+    }
+    .instrument(span)
+    .await
+  }
 
-      pending.write().remove(&object.id);
-    });
+  /// Gives up on terminating an object, which gets released as is.
+  async fn abandon(&self, object: &Object<O>, attempts: u32) {
+    let message = format!("gave up terminating after {attempts} attempts");
+    log::error!("{message}: '{}'", object.name());
+    self
+      .command
+      .recorder::<O>(object.name().to_owned())
+      .warning("TerminateAbandoned", message)
+      .await
+      .ok();
+  }
+
+  /// Backs off requeues of objects stuck in a hot loop.
+  fn throttle(&self, object: &Object<O>, requeue: Duration) -> Duration {
+    let Some(hot_loop) = self.limits.throttle(object.id, requeue) else {
+      return requeue;
+    };
 
-    self.pending.write().insert(object.id);
+    if hot_loop.detected {
+      log::warning!(
+        "{} '{}' requeues in a hot loop, throttling it",
+        O::kind(),
+        object.name()
+      );
+    }
+    self.metrics.hot_loop(O::kind());
+
+    hot_loop.delay
   }
 }
+
+pub(crate) fn retry_delay(attempt: u32) -> Duration {
+  RETRY_BASE_DELAY
+    .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+    .min(RETRY_MAX_DELAY)
+}
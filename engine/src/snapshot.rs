@@ -0,0 +1,221 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Arc, time::Duration};
+
+use gusto_core::{util::Safe, ObjectKind, ObjectName, ObjectRef, ObjectStatus};
+use parking_lot::RwLock;
+
+use crate::{DynStore, ObjectId, Owned, Owners, Priority};
+
+/// ReconcileState
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReconcileState {
+  Idle,
+  /// A reconciliation waits in the work queue for a free slot.
+  Waiting {
+    priority: Priority,
+  },
+  /// A reconciliation is running, `queued` tells whether a newer version of
+  /// the object waits for it to finish.
+  Reconciling {
+    queued: bool,
+  },
+  /// A requeue or retry is scheduled to fire after the given duration.
+  Scheduled {
+    after: Duration,
+  },
+}
+
+/// OperatorObject
+pub(crate) struct OperatorObject {
+  pub id: ObjectId,
+  pub reconcile: ReconcileState,
+}
+
+/// Inspect
+///
+/// Exposes what an operator knows about its objects.
+pub(crate) trait Inspect: Safe {
+  fn objects(&self) -> BTreeMap<ObjectName, OperatorObject>;
+}
+
+/// ObjectSnapshot
+#[derive(Clone, Debug)]
+pub struct ObjectSnapshot {
+  pub name: ObjectName,
+  /// Missing for kinds without a controller, or while the object is being
+  /// created.
+  pub id: Option<ObjectId>,
+  pub status: ObjectStatus,
+  /// Debug rendering of the stored props.
+  pub props: String,
+  pub reconcile: Option<ReconcileState>,
+  pub owner: Option<ObjectRef>,
+}
+
+/// KindSnapshot
+#[derive(Clone, Debug)]
+pub struct KindSnapshot {
+  pub kind: ObjectKind,
+  pub controller: bool,
+  pub objects: Vec<ObjectSnapshot>,
+}
+
+/// Snapshot
+///
+/// Point in time view of the engine. Kinds are captured one after the other,
+/// so a snapshot taken while the engine is busy may not be consistent across
+/// kinds.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+  pub kinds: Vec<KindSnapshot>,
+  /// Ownership graph, as owners and the objects they own.
+  pub owners: BTreeMap<ObjectRef, Vec<Owned>>,
+}
+
+impl Snapshot {
+  pub fn kind(&self, kind: ObjectKind) -> Option<&KindSnapshot> {
+    self.kinds.iter().find(|k| k.kind == kind)
+  }
+
+  pub fn objects(
+    &self,
+  ) -> impl Iterator<Item = (ObjectKind, &ObjectSnapshot)> + '_ {
+    self
+      .kinds
+      .iter()
+      .flat_map(|k| k.objects.iter().map(move |o| (k.kind, o)))
+  }
+
+  /// Renders the ownership graph in the Graphviz DOT format.
+  pub fn to_dot(&self) -> String {
+    let mut dot = String::from("digraph owners {\n");
+    for (kind, object) in self.objects() {
+      writeln!(
+        dot,
+        "  \"{}\" [label=\"{}\\n{}\"];",
+        escape(&node_id(kind, &object.name)),
+        escape(&object.name),
+        escape(kind),
+      )
+      .ok();
+    }
+
+    for (owner, owned) in &self.owners {
+      let owner_id = node_id(owner.kind, &owner.name);
+      for owned in owned {
+        writeln!(
+          dot,
+          "  \"{}\" -> \"{}\";",
+          escape(&owner_id),
+          escape(&node_id(owned.kind, &owned.name)),
+        )
+        .ok();
+      }
+    }
+    dot.push_str("}\n");
+
+    dot
+  }
+}
+
+/// Inspector
+///
+/// Builds snapshots of the engine, including while it is running.
+#[derive(Clone)]
+pub struct Inspector {
+  kinds: Arc<RwLock<BTreeMap<ObjectKind, InspectedKind>>>,
+  owners: Arc<RwLock<Owners>>,
+}
+
+/// InspectedKind
+#[derive(Clone)]
+struct InspectedKind {
+  store: Arc<DynStore>,
+  operator: Option<Arc<dyn Inspect>>,
+}
+
+impl Inspector {
+  pub(crate) fn new(owners: Arc<RwLock<Owners>>) -> Self {
+    Self {
+      kinds: Default::default(),
+      owners,
+    }
+  }
+
+  pub(crate) fn register_store(&self, kind: ObjectKind, store: Arc<DynStore>) {
+    self.kinds.write().entry(kind).or_insert(InspectedKind {
+      store,
+      operator: None,
+    });
+  }
+
+  pub(crate) fn register_operator(
+    &self,
+    kind: ObjectKind,
+    operator: Arc<dyn Inspect>,
+  ) {
+    if let Some(inspected) = self.kinds.write().get_mut(kind) {
+      inspected.operator = Some(operator);
+    }
+  }
+
+  pub(crate) fn unregister_operator(&self, kind: ObjectKind) {
+    if let Some(inspected) = self.kinds.write().get_mut(kind) {
+      inspected.operator = None;
+    }
+  }
+
+  pub fn snapshot(&self) -> Snapshot {
+    let kinds = self.kinds.read().clone();
+    let owners = self.owners.read();
+
+    let kinds = kinds
+      .into_iter()
+      .map(|(kind, inspected)| {
+        let mut operator_objects = inspected
+          .operator
+          .as_ref()
+          .map(|operator| operator.objects())
+          .unwrap_or_default();
+
+        let objects = inspected
+          .store
+          .statuses()
+          .into_iter()
+          .map(|(name, status, props)| {
+            let object = operator_objects.remove(&name);
+            ObjectSnapshot {
+              id: object.as_ref().map(|o| o.id),
+              reconcile: object.map(|o| o.reconcile),
+              owner: owners.owner_of(kind, &name).cloned(),
+              name,
+              status,
+              props,
+            }
+          })
+          .collect();
+
+        KindSnapshot {
+          kind,
+          controller: inspected.operator.is_some(),
+          objects,
+        }
+      })
+      .collect();
+
+    Snapshot {
+      kinds,
+      owners: owners
+        .iter()
+        .map(|(owner, owned)| (owner.clone(), owned.iter().cloned().collect()))
+        .collect(),
+    }
+  }
+}
+
+fn node_id(kind: ObjectKind, name: &ObjectName) -> String {
+  format!("{kind}/{name}")
+}
+
+fn escape(s: &str) -> String {
+  s.replace('\\', "\\\\").replace('"', "\\\"")
+}
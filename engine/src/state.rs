@@ -0,0 +1,179 @@
+use std::{
+  any::Any, collections::BTreeMap, future::Future, pin::Pin, sync::Arc
+};
+
+#[cfg(feature = "serde")]
+use gusto_core::PersistentState;
+use gusto_core::{
+  util::Safe, Error, ObjectDefinition, ObjectName, Result, StateReceiver
+};
+use parking_lot::RwLock;
+use tokio::sync::watch;
+
+#[cfg(feature = "serde")]
+use crate::Checkpoints;
+
+/// SnapshotFn
+pub type SnapshotFn<O> =
+  fn(&<O as ObjectDefinition>::State) -> <O as ObjectDefinition>::State;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+type RestoreFn<O> = Box<
+  dyn Fn(&ObjectName) -> BoxFuture<Result<Option<<O as ObjectDefinition>::State>>>
+    + Send
+    + Sync,
+>;
+type SaveFn<O> = Box<
+  dyn Fn(&ObjectName, &<O as ObjectDefinition>::State) -> BoxFuture<Result<()>>
+    + Send
+    + Sync,
+>;
+type RemoveFn = Box<dyn Fn(&ObjectName) -> BoxFuture<Result<()>> + Send + Sync>;
+
+/// SharedStates
+///
+/// Copies of object states, published so they can be read without waiting
+/// for reconciles holding the states themselves.
+pub(crate) struct SharedStates<O>
+where
+  O: ObjectDefinition,
+{
+  snapshot: SnapshotFn<O>,
+  states: RwLock<BTreeMap<ObjectName, watch::Sender<Arc<O::State>>>>,
+}
+
+impl<O> SharedStates<O>
+where
+  O: ObjectDefinition,
+{
+  pub fn new(snapshot: SnapshotFn<O>) -> Self {
+    Self {
+      snapshot,
+      states: Default::default(),
+    }
+  }
+
+  pub fn publish(&self, name: &ObjectName, state: &O::State) {
+    let state = Arc::new((self.snapshot)(state));
+
+    let mut states = self.states.write();
+    match states.get(name) {
+      Some(state_tx) => {
+        state_tx.send_replace(state);
+      }
+      None => {
+        states.insert(name.to_owned(), watch::channel(state).0);
+      }
+    }
+  }
+
+  /// Drops the state of a deleted object, closing its subscriptions.
+  pub fn remove(&self, name: &ObjectName) {
+    self.states.write().remove(name);
+  }
+
+  fn watch(&self, name: &ObjectName) -> Result<StateReceiver<O>> {
+    self
+      .states
+      .read()
+      .get(name)
+      .map(watch::Sender::subscribe)
+      .ok_or_else(|| {
+        Error::NotFound {
+          kind: O::kind(),
+          name: name.to_owned(),
+        }
+      })
+  }
+}
+
+/// AnyStates
+pub(crate) trait AnyStates: Safe {
+  fn watch(&self, name: &ObjectName) -> Result<Box<dyn Any + Send>>;
+}
+
+impl<O> AnyStates for SharedStates<O>
+where
+  O: ObjectDefinition,
+{
+  fn watch(&self, name: &ObjectName) -> Result<Box<dyn Any + Send>> {
+    Ok(Box::new(SharedStates::<O>::watch(self, name)?))
+  }
+}
+
+/// PersistedStates
+///
+/// Object states checkpointed after each successful reconcile, and restored
+/// in place of initializing them.
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub(crate) struct PersistedStates<O>
+where
+  O: ObjectDefinition,
+{
+  pub(crate) restore: RestoreFn<O>,
+  pub(crate) save: SaveFn<O>,
+  pub(crate) remove: RemoveFn,
+}
+
+#[cfg(feature = "serde")]
+impl<O> PersistedStates<O>
+where
+  O: ObjectDefinition,
+{
+  pub fn new(checkpoints: Arc<dyn Checkpoints>) -> Self
+  where
+    O::State: PersistentState,
+  {
+    let saves = checkpoints.clone();
+    let removes = checkpoints.clone();
+
+    Self {
+      restore: Box::new(move |name| {
+        let checkpoints = checkpoints.clone();
+        let name = name.clone();
+        Box::pin(async move {
+          let Some(state) = checkpoints.load(O::kind(), &name).await? else {
+            return Ok(None);
+          };
+
+          serde_json::from_value(state)
+            .map(Some)
+            .map_err(|e| Error::checkpoint(&name, e.into()))
+        })
+      }),
+      save: Box::new(move |name, state| {
+        let saves = saves.clone();
+        let name = name.clone();
+        let state = serde_json::to_value(state);
+        Box::pin(async move {
+          let state = state.map_err(|e| Error::checkpoint(&name, e.into()))?;
+          saves.save(O::kind(), &name, state).await
+        })
+      }),
+      remove: Box::new(move |name| {
+        let removes = removes.clone();
+        let name = name.clone();
+        Box::pin(async move { removes.remove(O::kind(), &name).await })
+      }),
+    }
+  }
+}
+
+impl<O> PersistedStates<O>
+where
+  O: ObjectDefinition,
+{
+  /// Returns the last checkpointed state of an object, if any.
+  pub async fn restore(&self, name: &ObjectName) -> Result<Option<O::State>> {
+    (self.restore)(name).await
+  }
+
+  pub async fn save(&self, name: &ObjectName, state: &O::State) -> Result<()> {
+    (self.save)(name, state).await
+  }
+
+  /// Drops the checkpoint of a deleted object.
+  pub async fn remove(&self, name: &ObjectName) -> Result<()> {
+    (self.remove)(name).await
+  }
+}
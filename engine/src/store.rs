@@ -1,11 +1,17 @@
-use std::{any::Any, collections::BTreeMap, sync::Arc};
+use std::{
+  any::Any, collections::{BTreeMap, BTreeSet}, sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering}, Arc
+  }
+};
 
-use anyhow::{anyhow, bail, Result};
 use flume::{Receiver, Sender};
 use gusto_core::{
-  util::Safe, DynObjectManifest, ObjectDefinition, ObjectManifest, ObjectName
+  util::{apply_patch, Safe}, Condition, DynObjectManifest, Error, ObjectDefinition, ObjectManifest, ObjectName, ObjectStatus, PatchFn, Plan, PlannedChange, PropsDiff, Result
+};
+use parking_lot::{
+  MappedRwLockReadGuard, RwLock, RwLockReadGuard, RwLockWriteGuard
 };
-use parking_lot::RwLock;
+use tokio::sync::watch;
 
 /// StoreEvent
 pub struct StoreEvent<O>
@@ -37,6 +43,65 @@ where
   }
 }
 
+/// Watcher
+pub type Watcher<O> = Box<dyn Fn(&StoreEvent<O>) + Send + Sync>;
+
+/// WatcherId
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WatcherId(u64);
+
+/// ReleaseHook
+pub type ReleaseHook = Box<dyn Fn(&ObjectName) + Send + Sync>;
+
+/// IndexFn
+pub type IndexFn<O> =
+  Box<dyn Fn(&ObjectManifest<O>) -> Vec<String> + Send + Sync>;
+
+struct Index<O>
+where
+  O: ObjectDefinition,
+{
+  func: IndexFn<O>,
+  entries: BTreeMap<String, BTreeSet<ObjectName>>,
+}
+
+impl<O> Index<O>
+where
+  O: ObjectDefinition,
+{
+  fn add(&mut self, manifest: &ObjectManifest<O>) {
+    for key in (self.func)(manifest) {
+      self
+        .entries
+        .entry(key)
+        .or_default()
+        .insert(manifest.name().to_owned());
+    }
+  }
+
+  fn remove(&mut self, manifest: &ObjectManifest<O>) {
+    for key in (self.func)(manifest) {
+      if let Some(names) = self.entries.get_mut(&key) {
+        names.remove(manifest.name());
+        if names.is_empty() {
+          self.entries.remove(&key);
+        }
+      }
+    }
+  }
+}
+
+type Manifests<'a, O> =
+  RwLockWriteGuard<'a, BTreeMap<ObjectName, ObjectManifest<O>>>;
+
+/// Written
+#[derive(Clone, Copy, Debug)]
+pub struct Written {
+  pub generation: u64,
+  /// Whether the manifest differed from the stored one.
+  pub changed: bool,
+}
+
 /// Change
 #[derive(Clone, Debug)]
 pub enum Change {
@@ -50,6 +115,20 @@ where
   O: ObjectDefinition,
 {
   manifests: RwLock<BTreeMap<ObjectName, ObjectManifest<O>>>,
+  /// Props as last written, before admission altered them, which writes are
+  /// compared against to detect no-ops.
+  inputs: RwLock<BTreeMap<ObjectName, O::Props>>,
+  /// Bumped on every write of an object's props, admission included, unlike
+  /// its generation. Taken from a store-wide counter, so recreated objects
+  /// don't reuse them.
+  revisions: RwLock<BTreeMap<ObjectName, u64>>,
+  next_revision: AtomicU64,
+  statuses: RwLock<BTreeMap<ObjectName, watch::Sender<ObjectStatus>>>,
+  managed: AtomicBool,
+  watchers: RwLock<BTreeMap<WatcherId, Watcher<O>>>,
+  next_watcher: AtomicU64,
+  release_hooks: RwLock<Vec<ReleaseHook>>,
+  indexes: RwLock<BTreeMap<&'static str, Index<O>>>,
   event_tx: Sender<StoreEvent<O>>,
   event_rx: Receiver<StoreEvent<O>>,
 }
@@ -58,47 +137,388 @@ impl<O> Store<O>
 where
   O: ObjectDefinition,
 {
-  pub fn insert(&self, manifest: ObjectManifest<O>) -> Result<()> {
-    let prev = self
-      .manifests
+  /// Writes a manifest, unless it's identical to the one last written, as
+  /// admission may have altered the stored one. In that case no event is
+  /// emitted and the generation is kept.
+  pub fn insert(&self, manifest: ObjectManifest<O>) -> Result<Written> {
+    self.write(self.manifests.write(), manifest)
+  }
+
+  /// Applies a patch to a copy of the stored manifest and writes the
+  /// result, unless another write, or admission, came in meanwhile. The
+  /// patch runs without holding the store lock, and can't rename the object.
+  /// Like inserts, the result is compared against the manifest last written.
+  pub fn modify(
+    &self,
+    name: &ObjectName,
+    patch: PatchFn,
+  ) -> Result<(Written, ObjectManifest<O>)> {
+    let (current, revision) = {
+      let manifests = self.manifests.read();
+      let current = manifests.get(name).cloned().ok_or_else(|| {
+        Error::NotFound {
+          kind: O::kind(),
+          name: name.to_owned(),
+        }
+      })?;
+      (current, self.revision(name))
+    };
+
+    let mut patched: Box<DynObjectManifest> = Box::new(current.clone());
+    apply_patch(name, &mut *patched, patch)?;
+    let mut manifest = Box::into_inner(patched.as_manifest::<O>()?);
+    manifest.meta = current.meta;
+
+    let manifests = self.manifests.write();
+    if !manifests.contains_key(name) || self.revision(name) != revision {
+      return Err(Error::Conflict {
+        name: name.to_owned(),
+        reason: "manifest was written while being patched".to_owned(),
+      });
+    }
+
+    let written = self.write(manifests, manifest.clone())?;
+    manifest.meta.generation = written.generation;
+
+    Ok((written, manifest))
+  }
+
+  fn write(
+    &self,
+    mut manifests: Manifests<'_, O>,
+    mut manifest: ObjectManifest<O>,
+  ) -> Result<Written> {
+    let mut inputs = self.inputs.write();
+    if let Some(prev) = manifests.get(manifest.name()) {
+      let input = inputs.get(manifest.name()).unwrap_or(&prev.props);
+      if O::same_props(input, &manifest.props) {
+        return Ok(Written {
+          generation: prev.meta.generation,
+          changed: false,
+        });
+      }
+    }
+    inputs.insert(manifest.name().to_owned(), manifest.props.clone());
+    drop(inputs);
+    self.bump_revision(manifest.name());
+
+    manifest.meta.generation = manifests
+      .get(manifest.name())
+      .map_or(1, |prev| prev.meta.generation + 1);
+
+    let prev = manifests.insert(manifest.name().to_owned(), manifest.clone());
+    self.reindex(prev.as_ref(), Some(&manifest));
+    drop(manifests);
+
+    let generation = manifest.meta.generation;
+    self
+      .statuses
       .write()
-      .insert(manifest.name().to_owned(), manifest.clone());
+      .entry(manifest.name().to_owned())
+      .or_insert_with(|| watch::channel(Default::default()).0)
+      .send_modify(|status| {
+        status.generation = generation;
+        status.managed = self.is_managed();
+      });
 
     match prev {
-      Some(_) => {
-        self
-          .event_tx
-          .send(StoreEvent::new(Change::Update, manifest))
-      }
-      None => {
-        self
-          .event_tx
-          .send(StoreEvent::new(Change::Create, manifest))
+      Some(_) => self.emit(StoreEvent::new(Change::Update, manifest)),
+      None => self.emit(StoreEvent::new(Change::Create, manifest)),
+    }?;
+
+    Ok(Written {
+      generation,
+      changed: true,
+    })
+  }
+
+  /// Plans the insertion of a manifest, without writing it. Like writes,
+  /// the manifest is compared against the one last written, before
+  /// admission, while the diff of an update is against the stored props.
+  pub fn plan_insert(&self, manifest: &ObjectManifest<O>) -> Plan {
+    let input = self.inputs.read().get(manifest.name()).cloned();
+    let (change, diff) = match self.get(manifest.name()) {
+      Some(current) => {
+        let input = input.as_ref().unwrap_or(&current.props);
+        match O::same_props(input, &manifest.props) {
+          true => (PlannedChange::Unchanged, None),
+          false => {
+            let diff = O::diff(&current.props, &manifest.props);
+            (PlannedChange::Update, diff)
+          }
+        }
       }
+      None => (PlannedChange::Create, None),
+    };
+
+    Plan {
+      kind: O::kind(),
+      name: manifest.name().to_owned(),
+      change,
+      diff,
+      cascade: Vec::new(),
     }
-    .map_err(|_| anyhow!("closed channel"))?;
+  }
 
-    Ok(())
+  /// Diffs the stored props against the ones of a manifest, e.g. once
+  /// admitted.
+  pub fn diff(&self, manifest: &ObjectManifest<O>) -> Option<PropsDiff> {
+    let current = self.get(manifest.name())?;
+    O::diff(&current.props, &manifest.props)
+  }
+
+  fn revision(&self, name: &ObjectName) -> Option<u64> {
+    self.revisions.read().get(name).copied()
+  }
+
+  fn bump_revision(&self, name: &ObjectName) {
+    let revision = self.next_revision.fetch_add(1, Ordering::Relaxed);
+    self.revisions.write().insert(name.to_owned(), revision);
+  }
+
+  pub fn contains(&self, name: &ObjectName) -> bool {
+    self.manifests.read().contains_key(name)
+  }
+
+  pub fn get(&self, name: &ObjectName) -> Option<ObjectManifest<O>> {
+    self.manifests.read().get(name).cloned()
+  }
+
+  pub fn list(&self) -> Vec<ObjectManifest<O>> {
+    self.manifests.read().values().cloned().collect()
   }
 
   pub fn patch(&self, manifest: ObjectManifest<O>) -> Result<()> {
     let name = manifest.name();
 
-    if let Some(existing) = self.manifests.write().get_mut(name) {
+    let mut manifests = self.manifests.write();
+    if let Some(existing) = manifests.get_mut(name) {
+      let prev = existing.clone();
+      let generation = existing.meta.generation;
       *existing = manifest;
+      existing.meta.generation = generation;
+      self.bump_revision(&prev.meta.name);
+      self.reindex(Some(&prev), Some(existing));
     } else {
-      bail!("cannot patch, not manifest found with name {name}")
+      return Err(Error::NotFound {
+        kind: O::kind(),
+        name: name.to_owned(),
+      });
     }
 
     Ok(())
   }
 
   pub fn remove(&self, name: &ObjectName) -> Result<()> {
-    if let Some(removed) = self.manifests.write().remove(name) {
-      self
-        .event_tx
-        .send(StoreEvent::new(Change::Delete, removed))
-        .map_err(|_| anyhow!("closed channel"))?;
+    if !self.managed.load(Ordering::Acquire) {
+      self.drop_status(name);
+    }
+
+    let mut manifests = self.manifests.write();
+    let removed = manifests.remove(name);
+    self.inputs.write().remove(name);
+    self.revisions.write().remove(name);
+    self.reindex(removed.as_ref(), None);
+    drop(manifests);
+
+    if let Some(removed) = removed {
+      self.emit(StoreEvent::new(Change::Delete, removed))?;
+    }
+
+    Ok(())
+  }
+
+  /// Adds an index, keyed by the values the function returns for each
+  /// object. Existing objects are indexed right away.
+  pub fn add_index(
+    &self,
+    name: &'static str,
+    func: impl Fn(&ObjectManifest<O>) -> Vec<String> + Send + Sync + 'static,
+  ) {
+    let manifests = self.manifests.read();
+    let mut index = Index {
+      func: Box::new(func),
+      entries: Default::default(),
+    };
+    for manifest in manifests.values() {
+      index.add(manifest);
+    }
+
+    self.indexes.write().insert(name, index);
+  }
+
+  /// Returns the objects the given index maps to the key.
+  pub fn by_index(
+    &self,
+    index: &str,
+    key: &str,
+  ) -> Result<Vec<ObjectManifest<O>>> {
+    let manifests = self.manifests.read();
+    let indexes = self.indexes.read();
+    let index = indexes.get(index).ok_or_else(|| {
+      Error::IndexNotRegistered {
+        kind: O::kind(),
+        index: index.to_owned(),
+      }
+    })?;
+
+    Ok(
+      index
+        .entries
+        .get(key)
+        .into_iter()
+        .flatten()
+        .filter_map(|name| manifests.get(name).cloned())
+        .collect(),
+    )
+  }
+
+  /// Keeps indexes in sync with a write. Must be called with the manifests
+  /// lock held, which is always taken before the indexes one.
+  fn reindex(
+    &self,
+    prev: Option<&ObjectManifest<O>>,
+    next: Option<&ObjectManifest<O>>,
+  ) {
+    let mut indexes = self.indexes.write();
+    for index in indexes.values_mut() {
+      if let Some(prev) = prev {
+        index.remove(prev);
+      }
+      if let Some(next) = next {
+        index.add(next);
+      }
+    }
+  }
+
+  /// Marks the store as managed by an operator, which then receives its
+  /// events and becomes responsible for releasing statuses once objects are
+  /// terminated. Unmanaged stores drop events and statuses right away.
+  pub fn set_managed(&self, managed: bool) {
+    self.managed.store(managed, Ordering::Release);
+
+    for status in self.statuses.read().values() {
+      status.send_if_modified(|status| {
+        let changed = status.managed != managed;
+        status.managed = managed;
+        changed
+      });
+    }
+  }
+
+  pub fn is_managed(&self) -> bool {
+    self.managed.load(Ordering::Acquire)
+  }
+
+  /// Drops the status of a removed object, notifying its watchers.
+  pub fn release(&self, name: &ObjectName) {
+    if !self.manifests.read().contains_key(name) {
+      self.drop_status(name);
+    }
+  }
+
+  /// Calls the given function whenever the status of a removed object is
+  /// dropped, e.g. to forget its events.
+  pub fn on_release(&self, hook: impl Fn(&ObjectName) + Send + Sync + 'static) {
+    self.release_hooks.write().push(Box::new(hook));
+  }
+
+  fn drop_status(&self, name: &ObjectName) {
+    let removed = self.statuses.write().remove(name);
+    if removed.is_some() {
+      for hook in self.release_hooks.read().iter() {
+        hook(name);
+      }
+    }
+  }
+
+  pub fn set_condition(
+    &self,
+    name: &ObjectName,
+    condition: Condition,
+  ) -> Result<()> {
+    self
+      .status(name)?
+      .send_if_modified(|status| status.conditions.set(condition));
+
+    Ok(())
+  }
+
+  pub fn set_reconciled(&self, name: &ObjectName, generation: u64) {
+    if let Ok(status) = self.status(name) {
+      status.send_if_modified(|status| {
+        if status.reconciled_generation < generation {
+          status.reconciled_generation = generation;
+          true
+        } else {
+          false
+        }
+      });
+    }
+  }
+
+  pub fn watch_status(
+    &self,
+    name: &ObjectName,
+  ) -> Result<watch::Receiver<ObjectStatus>> {
+    Ok(self.status(name)?.subscribe())
+  }
+
+  /// Returns the status of every stored object, along with the debug
+  /// rendering of its props.
+  pub fn statuses(&self) -> Vec<(ObjectName, ObjectStatus, String)> {
+    let manifests = self.manifests.read();
+    let statuses = self.statuses.read();
+
+    manifests
+      .iter()
+      .filter_map(|(name, manifest)| {
+        let status = statuses.get(name)?.borrow().clone();
+        Some((name.to_owned(), status, format!("{:?}", manifest.props)))
+      })
+      .collect()
+  }
+
+  fn status(
+    &self,
+    name: &ObjectName,
+  ) -> Result<MappedRwLockReadGuard<'_, watch::Sender<ObjectStatus>>> {
+    RwLockReadGuard::try_map(self.statuses.read(), |statuses| {
+      statuses.get(name)
+    })
+    .map_err(|_| {
+      Error::NotFound {
+        kind: O::kind(),
+        name: name.to_owned(),
+      }
+    })
+  }
+
+  /// Calls the given function on every change, e.g. to reconcile the owners
+  /// of changed objects, until unwatched.
+  pub fn watch(
+    &self,
+    watcher: impl Fn(&StoreEvent<O>) + Send + Sync + 'static,
+  ) -> WatcherId {
+    let id = WatcherId(self.next_watcher.fetch_add(1, Ordering::Relaxed));
+    self.watchers.write().insert(id, Box::new(watcher));
+    id
+  }
+
+  pub fn unwatch(&self, id: WatcherId) {
+    self.watchers.write().remove(&id);
+  }
+
+  /// Notifies watchers and sends an event to the operator, if any. Stores
+  /// without a controller have nobody to consume their events, which would
+  /// pile up in the channel and keep the engine from ever being idle.
+  fn emit(&self, event: StoreEvent<O>) -> Result<()> {
+    for watcher in self.watchers.read().values() {
+      watcher(&event);
+    }
+
+    if self.managed.load(Ordering::Acquire) {
+      self.event_tx.send(event)?;
     }
 
     Ok(())
@@ -107,6 +527,10 @@ where
   pub fn events(&self) -> Receiver<StoreEvent<O>> {
     self.event_rx.clone()
   }
+
+  pub fn pending_events(&self) -> usize {
+    self.event_rx.len()
+  }
 }
 
 impl<O> Default for Store<O>
@@ -117,6 +541,15 @@ where
     let (event_tx, event_rx) = flume::unbounded::<StoreEvent<O>>();
     Self {
       manifests: Default::default(),
+      inputs: Default::default(),
+      revisions: Default::default(),
+      next_revision: Default::default(),
+      statuses: Default::default(),
+      managed: Default::default(),
+      watchers: Default::default(),
+      next_watcher: Default::default(),
+      release_hooks: Default::default(),
+      indexes: Default::default(),
       event_tx,
       event_rx,
     }
@@ -125,22 +558,131 @@ where
 
 /// AnyStore
 pub trait AnyStore: Any + Safe {
-  fn insert(&self, manifest: Box<DynObjectManifest>) -> Result<()>;
+  /// Checks that the manifest is of the store's kind.
+  fn check(&self, manifest: &DynObjectManifest) -> Result<()>;
+  fn insert(&self, manifest: Box<DynObjectManifest>) -> Result<Written>;
+  fn modify(
+    &self,
+    name: &ObjectName,
+    patch: PatchFn,
+  ) -> Result<(Written, Box<DynObjectManifest>)>;
+  fn plan_insert(&self, manifest: &DynObjectManifest) -> Result<Plan>;
+  fn diff(&self, manifest: &DynObjectManifest) -> Result<Option<PropsDiff>>;
+  fn contains(&self, name: &ObjectName) -> bool;
+  fn get(&self, name: &ObjectName) -> Option<Box<DynObjectManifest>>;
+  fn list(&self) -> Vec<Box<DynObjectManifest>>;
+  fn by_index(
+    &self,
+    index: &str,
+    key: &str,
+  ) -> Result<Vec<Box<DynObjectManifest>>>;
   fn remove(&self, name: &ObjectName) -> Result<()>;
+  fn set_condition(
+    &self,
+    name: &ObjectName,
+    condition: Condition,
+  ) -> Result<()>;
+  fn watch_status(
+    &self,
+    name: &ObjectName,
+  ) -> Result<watch::Receiver<ObjectStatus>>;
+  fn statuses(&self) -> Vec<(ObjectName, ObjectStatus, String)>;
+  fn unwatch(&self, id: WatcherId);
 }
 
 impl<O> AnyStore for Store<O>
 where
   O: ObjectDefinition,
 {
-  fn insert(&self, manifest: Box<DynObjectManifest>) -> Result<()> {
+  fn check(&self, manifest: &DynObjectManifest) -> Result<()> {
+    manifest
+      .downcast_ref::<O>()
+      .ok_or(Error::Downcast(std::any::type_name::<O>()))?;
+    Ok(())
+  }
+
+  fn insert(&self, manifest: Box<DynObjectManifest>) -> Result<Written> {
     let manifest = Box::into_inner(manifest.as_manifest()?);
     Store::<O>::insert(self, manifest)
   }
 
+  fn modify(
+    &self,
+    name: &ObjectName,
+    patch: PatchFn,
+  ) -> Result<(Written, Box<DynObjectManifest>)> {
+    let (written, manifest) = Store::<O>::modify(self, name, patch)?;
+    Ok((written, Box::new(manifest)))
+  }
+
+  fn plan_insert(&self, manifest: &DynObjectManifest) -> Result<Plan> {
+    let manifest = manifest
+      .downcast_ref()
+      .ok_or(Error::Downcast(std::any::type_name::<O>()))?;
+    Ok(Store::<O>::plan_insert(self, manifest))
+  }
+
+  fn diff(&self, manifest: &DynObjectManifest) -> Result<Option<PropsDiff>> {
+    let manifest = manifest
+      .downcast_ref()
+      .ok_or(Error::Downcast(std::any::type_name::<O>()))?;
+    Ok(Store::<O>::diff(self, manifest))
+  }
+
+  fn contains(&self, name: &ObjectName) -> bool {
+    Store::<O>::contains(self, name)
+  }
+
+  fn get(&self, name: &ObjectName) -> Option<Box<DynObjectManifest>> {
+    Store::<O>::get(self, name).map(|manifest| Box::new(manifest) as _)
+  }
+
+  fn list(&self) -> Vec<Box<DynObjectManifest>> {
+    Store::<O>::list(self)
+      .into_iter()
+      .map(|manifest| Box::new(manifest) as _)
+      .collect()
+  }
+
+  fn by_index(
+    &self,
+    index: &str,
+    key: &str,
+  ) -> Result<Vec<Box<DynObjectManifest>>> {
+    Ok(
+      Store::<O>::by_index(self, index, key)?
+        .into_iter()
+        .map(|manifest| Box::new(manifest) as _)
+        .collect(),
+    )
+  }
+
   fn remove(&self, name: &ObjectName) -> Result<()> {
     Store::<O>::remove(self, name)
   }
+
+  fn set_condition(
+    &self,
+    name: &ObjectName,
+    condition: Condition,
+  ) -> Result<()> {
+    Store::<O>::set_condition(self, name, condition)
+  }
+
+  fn watch_status(
+    &self,
+    name: &ObjectName,
+  ) -> Result<watch::Receiver<ObjectStatus>> {
+    Store::<O>::watch_status(self, name)
+  }
+
+  fn statuses(&self) -> Vec<(ObjectName, ObjectStatus, String)> {
+    Store::<O>::statuses(self)
+  }
+
+  fn unwatch(&self, id: WatcherId) {
+    Store::<O>::unwatch(self, id)
+  }
 }
 
 impl dyn AnyStore + Send + Sync {
@@ -148,8 +690,7 @@ impl dyn AnyStore + Send + Sync {
   where
     O: ObjectDefinition,
   {
-    Arc::downcast(self)
-      .map_err(|_| anyhow!("cannot downcast to {}", std::any::type_name::<O>()))
+    Arc::downcast(self).map_err(|_| Error::Downcast(std::any::type_name::<O>()))
   }
 }
 
@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use anyhow::Result;
 use gusto_core::{
-  Command, Controller, ObjectDefinition, ObjectManifest, ObjectMeta
+  Command, Condition, ConditionStatus, Controller, ObjectDefinition, ObjectManifest, ObjectMeta
 };
 use gusto_engine::Engine;
 
@@ -38,13 +38,20 @@ impl Controller<Foo> for FooController {
     &self,
     manifest: &ObjectManifest<Foo>,
     state: &mut FooProps,
-    _command: &Command,
+    command: &Command,
   ) -> Result<Option<Duration>> {
     state.foo = manifest.props.foo;
     dbg!(&state);
     tokio::time::sleep(Duration::from_secs(1)).await;
 
-    Ok(Some(Duration::ZERO))
+    let ready = Condition::new(Condition::READY, ConditionStatus::True)
+      .with_reason("Reconciled")
+      .with_observed_generation(manifest.meta.generation);
+    command
+      .set_condition::<Foo>(manifest.name().to_owned(), ready)
+      .await?;
+
+    Ok(None)
   }
 }
 
@@ -62,22 +69,31 @@ async fn main() -> Result<()> {
       let mut manifest = ObjectManifest::<Foo> {
         meta: ObjectMeta {
           name: "proxy".into(),
+          ..Default::default()
         },
         props: FooProps { foo: true },
       };
 
-      command.insert_manifest(manifest.clone()).await?;
-      tokio::time::sleep(Duration::from_millis(500)).await;
+      let timeout = Duration::from_secs(5);
+
+      command
+        .insert_and_wait_reconciled(manifest.clone(), timeout)
+        .await?;
 
       manifest.props.foo = false;
-      command.insert_manifest(manifest.clone()).await?;
-      tokio::time::sleep(Duration::from_millis(500)).await;
+      command
+        .insert_and_wait_reconciled(manifest.clone(), timeout)
+        .await?;
 
       manifest.props.foo = true;
-      command.insert_manifest(manifest).await?;
-      tokio::time::sleep(Duration::from_millis(500)).await;
-
-      command.remove_manifest::<Foo>("proxy".into()).await
+      command
+        .insert_and_wait_reconciled(manifest, timeout)
+        .await?;
+
+      command.remove_manifest::<Foo>("proxy".into()).await?;
+      command
+        .wait_for_deletion::<Foo>("proxy".into(), timeout)
+        .await
     })
   )?;
 
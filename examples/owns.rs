@@ -4,7 +4,7 @@ use anyhow::Result;
 use gusto_core::{
   Command, Controller, ObjectDefinition, ObjectManifest, ObjectMeta, ObjectName
 };
-use gusto_engine::Engine;
+use gusto_engine::{ControllerOptions, Engine};
 
 #[derive(Clone, Debug)]
 #[allow(unused)]
@@ -57,17 +57,27 @@ impl Controller<Parent> for ParentController {
         let child_manifest = ObjectManifest::<Child> {
           meta: ObjectMeta {
             name: child_props.name.clone(),
+            ..Default::default()
           },
           props: child_props.clone(),
         };
 
         command
-          .insert_owned_manifest(manifest.name().to_owned(), child_manifest)
+          .insert_owned_manifest(manifest.object_ref(), child_manifest)
           .await?;
+        if let Some(recorder) = command.object_recorder() {
+          recorder
+            .normal(
+              "ChildCreated",
+              format!("created child {}", child_props.name),
+            )
+            .await?;
+        }
+        state.children.insert(child_props.name.clone());
       }
     }
 
-    Ok(Some(Duration::from_secs(1)))
+    Ok(None)
   }
 }
 
@@ -75,8 +85,11 @@ impl Controller<Parent> for ParentController {
 async fn main() -> Result<()> {
   let mut engine = Engine::default();
   engine.register_object::<Parent>();
-  engine.register_controller(ParentController)?;
   engine.register_object::<Child>();
+  engine.register_controller_with(
+    ParentController,
+    ControllerOptions::new().owns::<Child>(),
+  )?;
 
   let command = engine.command();
 
@@ -86,6 +99,7 @@ async fn main() -> Result<()> {
       let manifest = ObjectManifest::<Parent> {
         meta: ObjectMeta {
           name: "parent".into(),
+          ..Default::default()
         },
         props: ParentProps {
           children: vec![
@@ -0,0 +1,268 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use gusto_core::{
+  Command, Controller, ObjectDefinition, ObjectKind, ObjectManifest, ObjectName, ObjectRef, Result
+};
+use gusto_engine::{
+  Activity, ControllerOptions, DynStore, Engine, EngineHandle, Inspector, ObjectOptions, Owned, Snapshot, Store
+};
+
+const IDLE_ROUNDS: usize = 8;
+const MAX_ROUNDS: usize = 100_000;
+const TICK: Duration = Duration::from_millis(1);
+
+/// TestEngine
+///
+/// Must run on a current-thread runtime with a paused clock, either through
+/// [`crate::block_on`] or `#[tokio::test(start_paused = true)]`.
+pub struct TestEngine {
+  engine: Option<Engine>,
+  stores: BTreeMap<ObjectKind, Arc<DynStore>>,
+  command: Command,
+  handle: EngineHandle,
+  activity: Activity,
+  inspector: Inspector,
+}
+
+impl TestEngine {
+  pub fn new() -> Self {
+    let engine = Engine::default();
+
+    Self {
+      stores: Default::default(),
+      command: engine.command(),
+      handle: engine.handle(),
+      activity: engine.activity(),
+      inspector: engine.inspector(),
+      engine: Some(engine),
+    }
+  }
+
+  pub fn register_object<O>(&mut self)
+  where
+    O: ObjectDefinition,
+  {
+    self.register_object_with::<O>(ObjectOptions::default())
+  }
+
+  pub fn register_object_with<O>(&mut self, options: ObjectOptions<O>)
+  where
+    O: ObjectDefinition,
+  {
+    let engine = self.engine_mut();
+    engine.register_object_with(options);
+
+    let store = engine.store::<O>().expect("store was just registered");
+    self.stores.insert(O::kind(), store);
+  }
+
+  pub fn register_controller<O>(
+    &mut self,
+    controller: impl Controller<O>,
+  ) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    self.engine_mut().register_controller(controller)
+  }
+
+  pub fn register_controller_with<O>(
+    &mut self,
+    controller: impl Controller<O>,
+    options: ControllerOptions<O>,
+  ) -> Result<()>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .engine_mut()
+      .register_controller_with(controller, options)
+  }
+
+  /// Returns the underlying engine, which is only available until started.
+  pub fn engine_mut(&mut self) -> &mut Engine {
+    self.engine.as_mut().expect("engine is already started")
+  }
+
+  /// Returns a handle to register kinds and controllers. Registrations only
+  /// resolve once the engine is started.
+  pub fn handle(&self) -> EngineHandle {
+    self.handle.clone()
+  }
+
+  /// Returns a command handle. Acknowledged commands only resolve once the
+  /// engine is started.
+  pub fn command(&self) -> Command {
+    self.command.clone()
+  }
+
+  pub fn start(&mut self) {
+    if let Some(engine) = self.engine.take() {
+      tokio::spawn(engine.start());
+    }
+  }
+
+  /// Runs the engine until no command, event or reconcile is pending.
+  ///
+  /// Virtual time only moves forward while pending work waits on a timer, so
+  /// scheduled requeues and retries don't fire unless the clock is advanced.
+  pub async fn run_until_idle(&mut self) {
+    self.start();
+
+    let mut idle_rounds = 0;
+    for _ in 0..MAX_ROUNDS {
+      tokio::task::yield_now().await;
+
+      if self.activity.is_idle() {
+        idle_rounds += 1;
+        if idle_rounds == IDLE_ROUNDS {
+          return;
+        }
+      } else {
+        idle_rounds = 0;
+        tokio::time::sleep(TICK).await;
+      }
+    }
+
+    panic!("engine did not become idle after {MAX_ROUNDS} rounds");
+  }
+
+  /// Advances the clock, firing due requeues and retries, then runs the
+  /// engine until idle.
+  pub async fn advance(&mut self, duration: Duration) {
+    self.run_until_idle().await;
+    tokio::time::advance(duration).await;
+    self.run_until_idle().await;
+  }
+
+  pub fn store<O>(&self) -> Arc<Store<O>>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .stores
+      .get(O::kind())
+      .cloned()
+      .unwrap_or_else(|| panic!("kind {} is not registered", O::kind()))
+      .as_store()
+      .expect("store kind matches")
+  }
+
+  /// Captures every registered kind, object and ownership, including while
+  /// the engine runs.
+  pub fn snapshot(&self) -> Snapshot {
+    self.inspector.snapshot()
+  }
+
+  pub fn manifest<O>(&self, name: &str) -> Option<ObjectManifest<O>>
+  where
+    O: ObjectDefinition,
+  {
+    self.store::<O>().get(&name.into())
+  }
+
+  pub fn manifests<O>(&self) -> Vec<ObjectManifest<O>>
+  where
+    O: ObjectDefinition,
+  {
+    self.store::<O>().list()
+  }
+
+  #[track_caller]
+  pub fn assert_exists<O>(&self, name: &str)
+  where
+    O: ObjectDefinition,
+  {
+    assert!(
+      self.manifest::<O>(name).is_some(),
+      "expected {} '{name}' to exist",
+      O::kind()
+    );
+  }
+
+  #[track_caller]
+  pub fn assert_absent<O>(&self, name: &str)
+  where
+    O: ObjectDefinition,
+  {
+    assert!(
+      self.manifest::<O>(name).is_none(),
+      "expected {} '{name}' to be absent",
+      O::kind()
+    );
+  }
+
+  #[track_caller]
+  pub fn assert_count<O>(&self, count: usize)
+  where
+    O: ObjectDefinition,
+  {
+    let names = self
+      .manifests::<O>()
+      .iter()
+      .map(|m| m.name().to_string())
+      .collect::<Vec<_>>();
+
+    assert_eq!(
+      names.len(),
+      count,
+      "expected {count} {} objects, found {names:?}",
+      O::kind()
+    );
+  }
+
+  /// Asserts that the `P` object named `owner` owns the `O` object named
+  /// `name`.
+  #[track_caller]
+  pub fn assert_owned<P, O>(&self, owner: &str, name: &str)
+  where
+    P: ObjectDefinition,
+    O: ObjectDefinition,
+  {
+    assert!(
+      self.is_owned::<P, O>(owner, name),
+      "expected {} '{name}' to be owned by {} '{owner}'",
+      O::kind(),
+      P::kind()
+    );
+  }
+
+  #[track_caller]
+  pub fn assert_not_owned<P, O>(&self, owner: &str, name: &str)
+  where
+    P: ObjectDefinition,
+    O: ObjectDefinition,
+  {
+    assert!(
+      !self.is_owned::<P, O>(owner, name),
+      "expected {} '{name}' not to be owned by {} '{owner}'",
+      O::kind(),
+      P::kind()
+    );
+  }
+
+  fn is_owned<P, O>(&self, owner: &str, name: &str) -> bool
+  where
+    P: ObjectDefinition,
+    O: ObjectDefinition,
+  {
+    let owner = ObjectRef::new::<P>(owner.into());
+    let owned = Owned {
+      kind: O::kind(),
+      name: ObjectName::from(name),
+    };
+
+    self
+      .inspector
+      .snapshot()
+      .owners
+      .get(&owner)
+      .is_some_and(|owned_objects| owned_objects.contains(&owned))
+  }
+}
+
+impl Default for TestEngine {
+  fn default() -> Self {
+    Self::new()
+  }
+}
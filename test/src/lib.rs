@@ -0,0 +1,26 @@
+//! Test harness for Gusto controllers.
+//!
+//! The harness drives an [`Engine`] on a paused tokio clock, so tests can
+//! wait for the engine to settle and advance time deterministically instead
+//! of sleeping.
+
+use std::future::Future;
+
+pub use self::{engine::*, mock::*};
+pub use gusto_engine::Engine;
+
+mod engine;
+mod mock;
+
+/// Runs a future on a current-thread runtime with a paused clock.
+pub fn block_on<F>(future: F) -> F::Output
+where
+  F: Future,
+{
+  tokio::runtime::Builder::new_current_thread()
+    .enable_time()
+    .start_paused(true)
+    .build()
+    .expect("cannot build test runtime")
+    .block_on(future)
+}
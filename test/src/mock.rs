@@ -0,0 +1,580 @@
+use std::{collections::BTreeMap, fmt::Display, sync::Arc};
+
+use gusto_core::{
+  util::apply_patch, Batch, Command, CommandAction, CommandEvent, CommandHandler, CommandReply, Condition, ConditionStatus, DynObjectManifest, Error, EventType, ObjectDefinition, ObjectEvent, ObjectKind, ObjectManifest, ObjectName, ObjectRef, ObjectStatus, PatchFn, Plan, PlannedChange, Result
+};
+use parking_lot::Mutex;
+use tokio::sync::watch;
+
+type Responder = Arc<dyn Fn(&MockAction) -> Result<()> + Send + Sync>;
+
+/// MockAction
+#[derive(Clone)]
+pub enum MockAction {
+  Insert {
+    kind: ObjectKind,
+    name: ObjectName,
+    owner: Option<ObjectRef>,
+    manifest: Arc<DynObjectManifest>,
+  },
+  Remove {
+    kind: ObjectKind,
+    name: ObjectName,
+  },
+  /// A patch, along with the resulting manifest once applied.
+  Patch {
+    kind: ObjectKind,
+    name: ObjectName,
+    manifest: Option<Arc<DynObjectManifest>>,
+  },
+  RecordEvent(ObjectEvent),
+  SetCondition {
+    kind: ObjectKind,
+    name: ObjectName,
+    condition: Condition,
+  },
+  WatchStatus {
+    kind: ObjectKind,
+    name: ObjectName,
+  },
+  WatchState {
+    kind: ObjectKind,
+    name: ObjectName,
+  },
+  DryRun(Box<MockAction>),
+  Batch(Vec<MockAction>),
+}
+
+impl MockAction {
+  pub fn kind(&self) -> ObjectKind {
+    match self {
+      Self::Insert { kind, .. } => kind,
+      Self::Remove { kind, .. } => kind,
+      Self::Patch { kind, .. } => kind,
+      Self::RecordEvent(event) => event.kind,
+      Self::SetCondition { kind, .. } => kind,
+      Self::WatchStatus { kind, .. } => kind,
+      Self::WatchState { kind, .. } => kind,
+      Self::DryRun(action) => action.kind(),
+      Self::Batch(_) => Batch::KIND,
+    }
+  }
+
+  pub fn name(&self) -> &ObjectName {
+    match self {
+      Self::Insert { name, .. } => name,
+      Self::Remove { name, .. } => name,
+      Self::Patch { name, .. } => name,
+      Self::RecordEvent(event) => &event.name,
+      Self::SetCondition { name, .. } => name,
+      Self::WatchStatus { name, .. } => name,
+      Self::WatchState { name, .. } => name,
+      Self::DryRun(action) => action.name(),
+      Self::Batch(_) => Batch::name(),
+    }
+  }
+
+  pub fn manifest<O>(&self) -> Option<&ObjectManifest<O>>
+  where
+    O: ObjectDefinition,
+  {
+    match self {
+      Self::Insert { manifest, .. } => manifest.downcast_ref(),
+      Self::Patch {
+        manifest: Some(manifest),
+        ..
+      } => manifest.downcast_ref(),
+      _ => None,
+    }
+  }
+
+  fn from_action(action: CommandAction) -> (Self, Option<MockReply>) {
+    match action {
+      CommandAction::InsertManifest(kind, manifest, owner) => {
+        let action = Self::Insert {
+          kind,
+          name: manifest.name().to_owned(),
+          owner,
+          manifest: Arc::from(manifest),
+        };
+        (action, None)
+      }
+      CommandAction::RemoveManifest(kind, name) => {
+        (Self::Remove { kind, name }, None)
+      }
+      CommandAction::PatchManifest(kind, name, patch) => {
+        let action = Self::Patch {
+          kind,
+          name,
+          manifest: None,
+        };
+        (action, Some(MockReply::Patch(patch)))
+      }
+      CommandAction::RecordEvent(event) => (Self::RecordEvent(event), None),
+      CommandAction::SetCondition(kind, name, condition) => {
+        let action = Self::SetCondition {
+          kind,
+          name,
+          condition,
+        };
+        (action, None)
+      }
+      CommandAction::WatchStatus(kind, name, watch_tx) => {
+        let action = Self::WatchStatus { kind, name };
+        (action, Some(MockReply::WatchStatus(watch_tx)))
+      }
+      CommandAction::WatchState(kind, name, _) => {
+        (Self::WatchState { kind, name }, None)
+      }
+      CommandAction::DryRun(action) => {
+        let (action, reply) = Self::from_action(*action);
+        (Self::DryRun(Box::new(action)), reply)
+      }
+      CommandAction::Batch(actions) => {
+        let actions = actions
+          .into_iter()
+          .map(|action| Self::from_action(action).0)
+          .collect();
+        (Self::Batch(actions), None)
+      }
+    }
+  }
+}
+
+impl Display for MockAction {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let (kind, name) = (self.kind(), self.name());
+
+    match self {
+      Self::Insert { owner: None, .. } => write!(f, "insert {kind} {name}"),
+      Self::Insert {
+        owner: Some(owner), ..
+      } => {
+        write!(
+          f,
+          "insert {kind} {name} owned by {} {}",
+          owner.kind, owner.name
+        )
+      }
+      Self::Remove { .. } => write!(f, "remove {kind} {name}"),
+      Self::Patch { .. } => write!(f, "patch {kind} {name}"),
+      Self::RecordEvent(event) => {
+        let ty = match event.ty {
+          EventType::Normal => "normal",
+          EventType::Warning => "warning",
+        };
+        write!(
+          f,
+          "event {kind} {name} {ty} {}: {}",
+          event.reason, event.message
+        )
+      }
+      Self::SetCondition { condition, .. } => {
+        let status = match condition.status {
+          ConditionStatus::True => "true",
+          ConditionStatus::False => "false",
+          ConditionStatus::Unknown => "unknown",
+        };
+        write!(f, "condition {kind} {name} {}={status}", condition.ty)
+      }
+      Self::WatchStatus { .. } => write!(f, "watch {kind} {name}"),
+      Self::WatchState { .. } => write!(f, "watch-state {kind} {name}"),
+      Self::DryRun(action) => write!(f, "dry-run {action}"),
+      Self::Batch(actions) => {
+        write!(f, "batch")?;
+        for action in actions {
+          write!(f, "\n  {action}")?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+/// MockReply
+enum MockReply {
+  WatchStatus(catty::Sender<watch::Receiver<ObjectStatus>>),
+  Patch(PatchFn),
+  /// Outcome of a patch, applied outside the state lock.
+  Patched(Result<Box<DynObjectManifest>>),
+}
+
+/// MockState
+#[derive(Default)]
+struct MockState {
+  actions: Vec<MockAction>,
+  failures: Vec<Error>,
+  responder: Option<Responder>,
+  manifests: BTreeMap<(ObjectKind, ObjectName), Arc<DynObjectManifest>>,
+  statuses: BTreeMap<(ObjectKind, ObjectName), watch::Sender<ObjectStatus>>,
+}
+
+impl MockState {
+  fn apply(
+    &mut self,
+    action: &mut MockAction,
+    reply: Option<MockReply>,
+  ) -> Result<CommandReply> {
+    let key = (action.kind(), action.name().to_owned());
+    let res = match action {
+      MockAction::Insert { manifest, .. } => {
+        self.manifests.insert(key.clone(), manifest.clone());
+        let status = self
+          .statuses
+          .entry(key)
+          .or_insert_with(|| watch::channel(Default::default()).0);
+
+        // Mocked writes are considered reconciled right away.
+        let mut generation = 0;
+        status.send_modify(|status| {
+          status.generation += 1;
+          status.reconciled_generation = status.generation;
+          generation = status.generation;
+        });
+
+        CommandReply::Written { generation }
+      }
+      MockAction::Remove { .. } => {
+        self.manifests.remove(&key);
+        self.statuses.remove(&key);
+        CommandReply::Done
+      }
+      MockAction::Patch { manifest, .. } => {
+        let Some(MockReply::Patched(patched)) = reply else {
+          return Ok(CommandReply::Done);
+        };
+
+        let patched = patched?;
+        let patched_manifest = Arc::from(patched.clone_manifest());
+        self
+          .manifests
+          .insert(key.clone(), Arc::clone(&patched_manifest));
+        *manifest = Some(patched_manifest);
+
+        let mut generation = 0;
+        self.status(key)?.send_modify(|status| {
+          status.generation += 1;
+          status.reconciled_generation = status.generation;
+          generation = status.generation;
+        });
+
+        CommandReply::Patched {
+          generation,
+          manifest: patched,
+        }
+      }
+      MockAction::SetCondition { condition, .. } => {
+        self.status(key)?.send_modify(|status| {
+          status.conditions.set(condition.clone());
+        });
+        CommandReply::Done
+      }
+      MockAction::WatchStatus { .. } => {
+        let status = self.status(key)?;
+        if let Some(MockReply::WatchStatus(watch_tx)) = reply {
+          watch_tx.send(status.subscribe()).ok();
+        }
+        CommandReply::Done
+      }
+      // Mocked objects have no controller, hence no state.
+      MockAction::WatchState { kind, .. } => {
+        return Err(Error::StateNotShared(kind));
+      }
+      MockAction::RecordEvent(_) => CommandReply::Done,
+      MockAction::Batch(actions) => {
+        let batchable = |action: &MockAction| {
+          matches!(
+            action,
+            MockAction::Insert { .. } | MockAction::Remove { .. }
+          )
+        };
+        if let Some(action) = actions.iter().find(|a| !batchable(a)) {
+          return Err(Error::Conflict {
+            name: action.name().to_owned(),
+            reason: format!("{action} can't be batched"),
+          });
+        }
+
+        for action in actions {
+          self.apply(action, None)?;
+        }
+        CommandReply::Done
+      }
+      MockAction::DryRun(action) => {
+        let exists = self.statuses.contains_key(&key);
+        let change = match (action.as_ref(), exists) {
+          (MockAction::Insert { .. }, false) => PlannedChange::Create,
+          (MockAction::Insert { .. }, true) => PlannedChange::Update,
+          (MockAction::Remove { .. }, false) => PlannedChange::Absent,
+          (MockAction::Remove { .. }, true) => PlannedChange::Remove,
+          _ => {
+            return Err(Error::Conflict {
+              name: key.1,
+              reason: format!("{action} can't be dry run"),
+            });
+          }
+        };
+
+        CommandReply::Planned(Plan {
+          kind: key.0,
+          name: key.1,
+          change,
+          diff: None,
+          cascade: Vec::new(),
+        })
+      }
+    };
+
+    Ok(res)
+  }
+
+  /// Returns the latest manifest inserted or patched for the given object,
+  /// unless it was removed since.
+  fn manifest(
+    &self,
+    (kind, name): &(ObjectKind, ObjectName),
+  ) -> Result<Arc<DynObjectManifest>> {
+    self
+      .manifests
+      .get(&(*kind, name.to_owned()))
+      .cloned()
+      .ok_or_else(|| {
+        Error::NotFound {
+          kind,
+          name: name.to_owned(),
+        }
+      })
+  }
+
+  fn status(
+    &self,
+    (kind, name): (ObjectKind, ObjectName),
+  ) -> Result<&watch::Sender<ObjectStatus>> {
+    self
+      .statuses
+      .get(&(kind, name.clone()))
+      .ok_or(Error::NotFound { kind, name })
+  }
+}
+
+/// MockCommand
+///
+/// Records the actions issued through its [`Command`] instead of applying
+/// them, replying with scripted responses.
+#[derive(Clone, Default)]
+pub struct MockCommand {
+  state: Arc<Mutex<MockState>>,
+}
+
+impl MockCommand {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn command(&self) -> Command {
+    Command::with_handler(self.clone())
+  }
+
+  /// Fails the next command with the given error.
+  pub fn fail_next(&self, error: Error) {
+    self.state.lock().failures.push(error);
+  }
+
+  /// Replies to every command with the result of the given function. Steps
+  /// of batches are passed to it as well, before any of them is applied, so
+  /// that failing one fails the whole batch.
+  pub fn respond_with(
+    &self,
+    responder: impl Fn(&MockAction) -> Result<()> + Send + Sync + 'static,
+  ) {
+    self.state.lock().responder = Some(Arc::new(responder));
+  }
+
+  pub fn actions(&self) -> Vec<MockAction> {
+    self.state.lock().actions.clone()
+  }
+
+  pub fn clear(&self) {
+    self.state.lock().actions.clear();
+  }
+
+  /// Returns every manifest of the given kind that was inserted.
+  pub fn inserted<O>(&self) -> Vec<ObjectManifest<O>>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .state
+      .lock()
+      .actions
+      .iter()
+      .filter_map(|action| {
+        match action {
+          MockAction::Insert { .. } => action.manifest::<O>().cloned(),
+          _ => None,
+        }
+      })
+      .collect()
+  }
+
+  /// Returns every manifest of the given kind resulting from a patch.
+  pub fn patched<O>(&self) -> Vec<ObjectManifest<O>>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .state
+      .lock()
+      .actions
+      .iter()
+      .filter_map(|action| {
+        match action {
+          MockAction::Patch { .. } => action.manifest::<O>().cloned(),
+          _ => None,
+        }
+      })
+      .collect()
+  }
+
+  /// Returns every owned manifest of the given kind that was inserted, along
+  /// with its owner.
+  pub fn inserted_owned<O>(&self) -> Vec<(ObjectRef, ObjectManifest<O>)>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .state
+      .lock()
+      .actions
+      .iter()
+      .filter_map(|action| {
+        match action {
+          MockAction::Insert {
+            owner: Some(owner), ..
+          } => Some((owner.to_owned(), action.manifest::<O>()?.clone())),
+          _ => None,
+        }
+      })
+      .collect()
+  }
+
+  /// Returns the names of every object of the given kind that was removed.
+  pub fn removed<O>(&self) -> Vec<ObjectName>
+  where
+    O: ObjectDefinition,
+  {
+    self
+      .state
+      .lock()
+      .actions
+      .iter()
+      .filter_map(|action| {
+        match action {
+          MockAction::Remove { kind, name } if *kind == O::kind() => {
+            Some(name.to_owned())
+          }
+          _ => None,
+        }
+      })
+      .collect()
+  }
+
+  pub fn events(&self) -> Vec<ObjectEvent> {
+    self
+      .state
+      .lock()
+      .actions
+      .iter()
+      .filter_map(|action| {
+        match action {
+          MockAction::RecordEvent(event) => Some(event.clone()),
+          _ => None,
+        }
+      })
+      .collect()
+  }
+
+  /// Renders recorded actions, one per line.
+  pub fn render(&self) -> String {
+    self
+      .state
+      .lock()
+      .actions
+      .iter()
+      .map(|action| format!("{action}\n"))
+      .collect()
+  }
+
+  /// Asserts that the recorded actions match the expected rendering. Leading
+  /// and trailing whitespace of each line is ignored.
+  #[track_caller]
+  pub fn assert_golden(&self, expected: &str) {
+    let normalize = |s: &str| {
+      s.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+    };
+
+    let actual = normalize(&self.render());
+    let expected = normalize(expected);
+
+    assert!(
+      actual == expected,
+      "recorded actions don't match\n--- expected\n{expected}\n--- actual\n{actual}"
+    );
+  }
+
+  /// Calls the responder without holding the state lock, so that it can
+  /// inspect the mock itself.
+  fn respond(
+    &self,
+    action: &mut MockAction,
+    reply: Option<MockReply>,
+  ) -> Result<CommandReply> {
+    let responder = {
+      let mut state = self.state.lock();
+      if !state.failures.is_empty() {
+        return Err(state.failures.remove(0));
+      }
+      state.responder.clone()
+    };
+    if let Some(responder) = responder {
+      responder(action)?;
+      if let MockAction::Batch(steps) = action {
+        steps.iter().try_for_each(|step| responder(step))?;
+      }
+    }
+
+    // Like the engine, patches apply to a copy, outside the lock.
+    let reply = match reply {
+      Some(MockReply::Patch(patch)) => {
+        let key = (action.kind(), action.name().to_owned());
+        let current = self.state.lock().manifest(&key);
+        let patched = current.and_then(|current| {
+          let mut patched = current.clone_manifest();
+          apply_patch(&key.1, &mut *patched, patch)?;
+          Ok(patched)
+        });
+        Some(MockReply::Patched(patched))
+      }
+      reply => reply,
+    };
+
+    self.state.lock().apply(action, reply)
+  }
+}
+
+impl CommandHandler for MockCommand {
+  fn handle(&self, event: CommandEvent) {
+    let (mut action, reply) = MockAction::from_action(event.action);
+
+    let res = self.respond(&mut action, reply);
+    self.state.lock().actions.push(action);
+
+    if let Some(ack) = event.ack {
+      ack.send(res).ok();
+    }
+  }
+}
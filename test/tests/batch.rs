@@ -0,0 +1,139 @@
+use gusto_core::{Error, ObjectDefinition, ObjectRef};
+use gusto_test::{MockCommand, TestEngine};
+
+use self::common::manifest;
+
+mod common;
+
+struct Item;
+impl ObjectDefinition for Item {
+  type Props = u32;
+}
+
+struct Unregistered;
+impl ObjectDefinition for Unregistered {
+  type Props = u32;
+}
+
+fn engine() -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Item>();
+  engine.start();
+  engine
+}
+
+#[tokio::test(start_paused = true)]
+async fn applies_every_step() {
+  let mut engine = engine();
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Item>("c", 1))
+    .await
+    .unwrap();
+  let mut batch = command.batch();
+  batch
+    .insert(manifest::<Item>("a", 1))
+    .insert(manifest::<Item>("b", 1))
+    .remove::<Item>("c".into());
+  batch.commit().await.unwrap();
+  engine.run_until_idle().await;
+
+  engine.assert_exists::<Item>("a");
+  engine.assert_exists::<Item>("b");
+  engine.assert_absent::<Item>("c");
+}
+
+#[tokio::test(start_paused = true)]
+async fn applies_nothing_when_a_kind_is_not_registered() {
+  let mut engine = engine();
+  let command = engine.command();
+
+  let mut batch = command.batch();
+  batch
+    .insert(manifest::<Item>("a", 1))
+    .insert(manifest::<Unregistered>("b", 1));
+  let res = batch.commit().await;
+  engine.run_until_idle().await;
+
+  assert!(matches!(res, Err(Error::KindNotRegistered(_))), "{res:?}");
+  engine.assert_absent::<Item>("a");
+}
+
+#[tokio::test(start_paused = true)]
+async fn applies_nothing_when_an_object_is_owned_already() {
+  let mut engine = engine();
+  let command = engine.command();
+
+  for owner in ["owner", "other"] {
+    command
+      .insert_manifest(manifest::<Item>(owner, 1))
+      .await
+      .unwrap();
+  }
+  command
+    .insert_owned_manifest(
+      ObjectRef::new::<Item>("owner".into()),
+      manifest::<Item>("owned", 1),
+    )
+    .await
+    .unwrap();
+
+  let mut batch = command.batch();
+  batch.insert(manifest::<Item>("a", 1)).insert_owned(
+    ObjectRef::new::<Item>("other".into()),
+    manifest::<Item>("owned", 2),
+  );
+  let res = batch.commit().await;
+  engine.run_until_idle().await;
+
+  assert!(matches!(res, Err(Error::AlreadyOwned { .. })), "{res:?}");
+  engine.assert_absent::<Item>("a");
+  engine.assert_owned::<Item, Item>("owner", "owned");
+  assert_eq!(engine.manifest::<Item>("owned").unwrap().props, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn removing_an_owner_removes_what_it_owns() {
+  let mut engine = engine();
+  let command = engine.command();
+
+  let owner = ObjectRef::new::<Item>("owner".into());
+  let mut batch = command.batch();
+  batch
+    .insert(manifest::<Item>("owner", 1))
+    .insert_owned(owner.clone(), manifest::<Item>("owned", 1));
+  batch.commit().await.unwrap();
+  engine.run_until_idle().await;
+  engine.assert_owned::<Item, Item>("owner", "owned");
+
+  let mut batch = command.batch();
+  batch.remove::<Item>("owner".into());
+  batch.commit().await.unwrap();
+  engine.run_until_idle().await;
+
+  engine.assert_count::<Item>(0);
+}
+
+#[tokio::test]
+async fn mock_fails_the_whole_batch() {
+  let mock = MockCommand::new();
+  mock.respond_with(|action| {
+    if **action.name() != *"b" {
+      return Ok(());
+    }
+
+    Err(Error::Conflict {
+      name: "b".into(),
+      reason: "rejected".into(),
+    })
+  });
+
+  let mut batch = mock.command().batch();
+  batch
+    .insert(manifest::<Item>("a", 1))
+    .insert(manifest::<Item>("b", 1));
+  assert!(batch.commit().await.is_err());
+
+  assert!(mock.inserted::<Item>().is_empty());
+}
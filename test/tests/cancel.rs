@@ -0,0 +1,213 @@
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering}, Arc
+  }, time::Duration
+};
+
+use anyhow::Result;
+use gusto_core::{Command, Controller, ObjectDefinition, ObjectManifest};
+use gusto_engine::ControllerOptions;
+use gusto_test::TestEngine;
+use tokio::time::Instant;
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+/// Reconciles of version zero wait until cancelled.
+struct Version;
+impl ObjectDefinition for Version {
+  type Props = u32;
+}
+
+struct VersionController {
+  calls: Calls,
+}
+
+#[async_trait::async_trait]
+impl Controller<Version> for VersionController {
+  async fn initialize_state(&self, _: &ObjectManifest<Version>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Version>,
+    _: &mut (),
+    command: &Command,
+  ) -> Result<Option<Duration>> {
+    let name = manifest.name();
+    if manifest.props == 0 {
+      command.cancellation().cancelled().await;
+      self.calls.push(format!("cancel {name}"));
+    } else {
+      self
+        .calls
+        .push(format!("reconcile {name} v{}", manifest.props));
+    }
+
+    Ok(None)
+  }
+
+  async fn terminate(
+    &self,
+    manifest: &ObjectManifest<Version>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    self.calls.push(format!("terminate {}", manifest.name()));
+    Ok(None)
+  }
+}
+
+/// Seconds the first reconcile takes, ignoring cancellation.
+struct Slow;
+impl ObjectDefinition for Slow {
+  type Props = u64;
+}
+
+#[derive(Clone, Default)]
+struct SlowController {
+  calls: Calls,
+  started: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl Controller<Slow> for SlowController {
+  async fn initialize_state(&self, _: &ObjectManifest<Slow>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Slow>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    let name = manifest.name();
+    self.calls.push(format!("start {name}"));
+    if !self.started.swap(true, Ordering::SeqCst) {
+      tokio::time::sleep(Duration::from_secs(manifest.props)).await;
+    }
+    self.calls.push(format!("end {name}"));
+
+    Ok(None)
+  }
+
+  async fn terminate(
+    &self,
+    manifest: &ObjectManifest<Slow>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    self.calls.push(format!("terminate {}", manifest.name()));
+    Ok(None)
+  }
+}
+
+fn engine(calls: &Calls) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Version>();
+  engine
+    .register_controller(VersionController {
+      calls: calls.clone(),
+    })
+    .unwrap();
+  engine.start();
+  engine
+}
+
+fn slow_engine(
+  controller: &SlowController,
+  options: ControllerOptions<Slow>,
+) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Slow>();
+  engine
+    .register_controller_with(controller.clone(), options)
+    .unwrap();
+  engine.start();
+  engine
+}
+
+#[tokio::test(start_paused = true)]
+async fn deletion_cancels_the_reconcile_before_terminating() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls);
+  let command = engine.command();
+
+  command
+    .insert_manifest_async(manifest::<Version>("a", 0))
+    .await
+    .unwrap();
+  // The engine isn't idle while the reconcile waits, so let it run instead.
+  tokio::time::sleep(Duration::from_millis(10)).await;
+  command
+    .remove_manifest::<Version>("a".into())
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  assert_eq!(calls.take(), ["cancel a", "terminate a"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn newer_generation_cancels_the_reconcile() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls);
+  let command = engine.command();
+
+  command
+    .insert_manifest_async(manifest::<Version>("a", 0))
+    .await
+    .unwrap();
+  tokio::time::sleep(Duration::from_millis(10)).await;
+  command
+    .insert_manifest(manifest::<Version>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  assert_eq!(calls.take(), ["cancel a", "reconcile a v1"]);
+  let status = command.watch_status::<Version>("a".into()).await.unwrap();
+  assert_eq!(status.borrow().reconciled_generation, 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn timed_out_reconciles_are_retried() {
+  let controller = SlowController::default();
+  let options =
+    ControllerOptions::default().reconcile_timeout(Duration::from_secs(1));
+  let mut engine = slow_engine(&controller, options);
+  let command = engine.command();
+
+  let started = Instant::now();
+  command
+    .insert_manifest(manifest::<Slow>("a", 3600))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  engine.advance(Duration::from_secs(1)).await;
+
+  assert_eq!(controller.calls.take(), ["start a", "start a", "end a"]);
+  assert!(started.elapsed() < Duration::from_secs(60));
+  let status = command.watch_status::<Slow>("a".into()).await.unwrap();
+  assert_eq!(status.borrow().reconciled_generation, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn terminate_waits_for_the_reconcile_to_stop() {
+  let controller = SlowController::default();
+  let mut engine = slow_engine(&controller, ControllerOptions::default());
+  let command = engine.command();
+
+  command
+    .insert_manifest_async(manifest::<Slow>("a", 5))
+    .await
+    .unwrap();
+  tokio::time::sleep(Duration::from_millis(10)).await;
+  command.remove_manifest::<Slow>("a".into()).await.unwrap();
+  engine.run_until_idle().await;
+
+  assert_eq!(controller.calls.take(), ["start a", "end a", "terminate a"]);
+}
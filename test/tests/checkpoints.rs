@@ -0,0 +1,128 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use gusto_core::{Command, Controller, ObjectDefinition, ObjectManifest};
+use gusto_engine::{ControllerOptions, FileCheckpoints};
+use gusto_test::TestEngine;
+use serde::{Deserialize, Serialize};
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+/// Counts its reconciles in a persisted state.
+struct Counter;
+impl ObjectDefinition for Counter {
+  type Props = u32;
+  type State = Count;
+}
+
+#[derive(Serialize, Deserialize)]
+struct Count {
+  reconciles: u32,
+}
+
+struct CounterController {
+  calls: Calls,
+}
+
+#[async_trait::async_trait]
+impl Controller<Counter> for CounterController {
+  async fn initialize_state(
+    &self,
+    manifest: &ObjectManifest<Counter>,
+  ) -> Result<Count> {
+    self.calls.push(format!("initialize {}", manifest.name()));
+    Ok(Count { reconciles: 0 })
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Counter>,
+    count: &mut Count,
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    count.reconciles += 1;
+    let name = manifest.name();
+    self
+      .calls
+      .push(format!("reconcile {name} #{}", count.reconciles));
+    Ok(None)
+  }
+}
+
+/// Starts an engine checkpointing states in the given directory, like a
+/// process restarting with an empty store.
+fn start(calls: &Calls, dir: &Path) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Counter>();
+  let checkpoints = Arc::new(FileCheckpoints::new(dir));
+  engine
+    .register_controller_with(
+      CounterController {
+        calls: calls.clone(),
+      },
+      ControllerOptions::default().persist_state(checkpoints),
+    )
+    .unwrap();
+  engine.start();
+  engine
+}
+
+async fn insert(engine: &mut TestEngine, version: u32) {
+  engine
+    .command()
+    .insert_manifest(manifest::<Counter>("a", version))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn restores_states_after_a_restart() {
+  let dir = tempfile::tempdir().unwrap();
+  let calls = Calls::default();
+
+  let mut engine = start(&calls, dir.path());
+  for version in 1..=3 {
+    insert(&mut engine, version).await;
+  }
+  assert_eq!(
+    calls.take(),
+    [
+      "initialize a",
+      "reconcile a #1",
+      "reconcile a #2",
+      "reconcile a #3"
+    ]
+  );
+
+  // Generations start over, so the manifest is older than the checkpoint.
+  let mut restarted = start(&calls, dir.path());
+  insert(&mut restarted, 3).await;
+  assert_eq!(calls.take(), ["reconcile a #4"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn initializes_recreated_objects() {
+  let dir = tempfile::tempdir().unwrap();
+  let calls = Calls::default();
+  let mut engine = start(&calls, dir.path());
+
+  insert(&mut engine, 1).await;
+  insert(&mut engine, 2).await;
+  engine
+    .command()
+    .remove_manifest::<Counter>("a".into())
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  calls.take();
+
+  insert(&mut engine, 1).await;
+  assert_eq!(calls.take(), ["initialize a", "reconcile a #1"]);
+
+  let mut restarted = start(&calls, dir.path());
+  insert(&mut restarted, 1).await;
+  assert_eq!(calls.take(), ["reconcile a #2"]);
+}
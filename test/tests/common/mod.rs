@@ -0,0 +1,39 @@
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use gusto_core::{ObjectDefinition, ObjectManifest, ObjectMeta};
+use parking_lot::Mutex;
+
+/// Calls
+///
+/// Controller calls, in the order they were made.
+#[derive(Clone, Default)]
+pub struct Calls(Arc<Mutex<Vec<String>>>);
+
+impl Calls {
+  pub fn push(&self, call: impl Into<String>) {
+    self.0.lock().push(call.into());
+  }
+
+  pub fn count(&self, call: &str) -> usize {
+    self.0.lock().iter().filter(|c| *c == call).count()
+  }
+
+  pub fn take(&self) -> Vec<String> {
+    std::mem::take(&mut *self.0.lock())
+  }
+}
+
+pub fn manifest<O>(name: &str, props: O::Props) -> ObjectManifest<O>
+where
+  O: ObjectDefinition,
+{
+  ObjectManifest {
+    meta: ObjectMeta {
+      name: name.into(),
+      ..Default::default()
+    },
+    props,
+  }
+}
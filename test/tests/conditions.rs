@@ -0,0 +1,185 @@
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Condition, ConditionStatus, Conditions, Controller, ObjectDefinition, ObjectManifest
+};
+use gusto_test::TestEngine;
+
+use self::common::manifest;
+
+mod common;
+
+/// Ready once reconciled, unless props are zero.
+struct Deployment;
+impl ObjectDefinition for Deployment {
+  type Props = u32;
+}
+
+struct DeploymentController;
+
+#[async_trait::async_trait]
+impl Controller<Deployment> for DeploymentController {
+  async fn initialize_state(
+    &self,
+    _: &ObjectManifest<Deployment>,
+  ) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Deployment>,
+    _: &mut (),
+    command: &Command,
+  ) -> Result<Option<Duration>> {
+    let status = match manifest.props {
+      0 => ConditionStatus::False,
+      _ => ConditionStatus::True,
+    };
+    let ready = Condition::new(Condition::READY, status)
+      .with_reason(format!("{} replicas", manifest.props))
+      .with_observed_generation(manifest.meta.generation);
+    command
+      .set_condition::<Deployment>(manifest.name().to_owned(), ready)
+      .await?;
+
+    Ok(None)
+  }
+}
+
+fn at(secs: u64) -> SystemTime {
+  SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn condition(ty: &str, status: ConditionStatus, secs: u64) -> Condition {
+  Condition {
+    last_transition_time: at(secs),
+    ..Condition::new(ty, status)
+  }
+}
+
+#[test]
+fn sets_conditions_by_type() {
+  let mut conditions = Conditions::default();
+
+  assert!(conditions.set(condition("Ready", ConditionStatus::False, 1)));
+  assert!(conditions.set(condition("Degraded", ConditionStatus::True, 1)));
+  assert!(!conditions.set(condition("Ready", ConditionStatus::False, 1)));
+  assert_eq!(conditions.len(), 2);
+
+  assert!(conditions.set(condition("Ready", ConditionStatus::True, 2)));
+  assert_eq!(conditions.len(), 2);
+  let ready = conditions.get("Ready").unwrap();
+  assert!(ready.is_true());
+  assert_eq!(ready.last_transition_time, at(2));
+  assert!(conditions.is_ready());
+
+  assert_eq!(conditions.remove("Degraded").unwrap().ty, "Degraded");
+  assert!(conditions.remove("Degraded").is_none());
+  assert_eq!(
+    conditions.iter().map(|c| c.ty.as_str()).collect::<Vec<_>>(),
+    ["Ready"]
+  );
+}
+
+#[test]
+fn keeps_transition_times_while_the_status_holds() {
+  let mut conditions = Conditions::default();
+  conditions.set(condition("Ready", ConditionStatus::True, 1));
+
+  // Same status at a later time is no change at all.
+  assert!(!conditions.set(condition("Ready", ConditionStatus::True, 2)));
+
+  // Other fields change, but the condition hasn't transitioned.
+  let scaled = condition("Ready", ConditionStatus::True, 3)
+    .with_reason("Scaled")
+    .with_message("3 replicas")
+    .with_observed_generation(2);
+  assert!(conditions.set(scaled));
+  let ready = conditions.get("Ready").unwrap();
+  assert_eq!(ready.reason, "Scaled");
+  assert_eq!(ready.message, "3 replicas");
+  assert_eq!(ready.observed_generation, 2);
+  assert_eq!(ready.last_transition_time, at(1));
+
+  assert!(conditions.set(condition("Ready", ConditionStatus::False, 4)));
+  assert_eq!(conditions.get("Ready").unwrap().last_transition_time, at(4));
+}
+
+#[test]
+fn reports_unknown_for_missing_conditions() {
+  let mut conditions = Conditions::default();
+  assert!(conditions.is_empty());
+  assert_eq!(conditions.status("Ready"), ConditionStatus::Unknown);
+  assert!(!conditions.is_true("Ready"));
+  assert!(!conditions.is_false("Ready"));
+  assert!(!conditions.is_ready());
+
+  conditions.set(Condition::new("Ready", ConditionStatus::Unknown));
+  let ready = conditions.get("Ready").unwrap();
+  assert!(ready.is_unknown());
+  assert!(!ready.is_true() && !ready.is_false());
+
+  conditions.set(Condition::new("Ready", ConditionStatus::False));
+  assert!(conditions.is_false("Ready"));
+  assert!(conditions.get("Ready").unwrap().is_false());
+}
+
+#[tokio::test(start_paused = true)]
+async fn sets_conditions_from_reconciles() {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Deployment>();
+  engine.register_controller(DeploymentController).unwrap();
+  engine.start();
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Deployment>("a", 0))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  let conditions = command.conditions::<Deployment>("a".into()).await.unwrap();
+  assert!(conditions.is_true(Condition::ACCEPTED));
+  let ready = conditions.get(Condition::READY).unwrap().clone();
+  assert!(ready.is_false());
+  assert_eq!(ready.reason, "0 replicas");
+  assert_eq!(ready.observed_generation, 1);
+
+  command
+    .insert_manifest(manifest::<Deployment>("a", 3))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  let conditions = command.conditions::<Deployment>("a".into()).await.unwrap();
+  let ready = conditions.get(Condition::READY).unwrap().clone();
+  assert!(ready.is_true());
+  assert_eq!(ready.reason, "3 replicas");
+  assert_eq!(ready.observed_generation, 2);
+
+  // Setting the same condition again doesn't notify status watchers.
+  let mut status = command
+    .watch_status::<Deployment>("a".into())
+    .await
+    .unwrap();
+  status.borrow_and_update();
+  command
+    .set_condition::<Deployment>("a".into(), ready.clone())
+    .await
+    .unwrap();
+  assert!(!status.has_changed().unwrap());
+
+  let fresh = Condition {
+    last_transition_time: SystemTime::now() + Duration::from_secs(60),
+    ..ready.clone()
+  };
+  command
+    .set_condition::<Deployment>("a".into(), fresh)
+    .await
+    .unwrap();
+  assert!(!status.has_changed().unwrap());
+  let conditions = command.conditions::<Deployment>("a".into()).await.unwrap();
+  assert_eq!(conditions.get(Condition::READY), Some(&ready));
+}
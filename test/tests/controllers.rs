@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{Command, Controller, ObjectDefinition, ObjectManifest};
+use gusto_engine::ControllerOptions;
+use gusto_test::TestEngine;
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+/// Seconds each reconcile takes.
+struct Plugin;
+impl ObjectDefinition for Plugin {
+  type Props = u64;
+}
+
+/// Watched by plugin controllers.
+struct Config;
+impl ObjectDefinition for Config {
+  type Props = u64;
+}
+
+/// Controller of a given version, prefixing its calls with it.
+struct PluginController {
+  version: u32,
+  calls: Calls,
+}
+
+impl PluginController {
+  fn new(version: u32, calls: &Calls) -> Self {
+    Self {
+      version,
+      calls: calls.clone(),
+    }
+  }
+
+  fn push(&self, call: &str, manifest: &ObjectManifest<Plugin>) {
+    let name = manifest.name();
+    self.calls.push(format!("v{} {call} {name}", self.version));
+  }
+}
+
+#[async_trait::async_trait]
+impl Controller<Plugin> for PluginController {
+  async fn admit_manifest(
+    &self,
+    manifest: ObjectManifest<Plugin>,
+  ) -> Result<ObjectManifest<Plugin>> {
+    self.push("admit", &manifest);
+    Ok(manifest)
+  }
+
+  async fn initialize_state(&self, _: &ObjectManifest<Plugin>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Plugin>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    self.push("reconcile", manifest);
+    tokio::time::sleep(Duration::from_secs(manifest.props)).await;
+    self.push("reconciled", manifest);
+    Ok(None)
+  }
+}
+
+fn watch_configs() -> ControllerOptions<Plugin> {
+  ControllerOptions::default().watches::<Config>(|_| vec!["a".into()])
+}
+
+#[tokio::test(start_paused = true)]
+async fn takes_over_objects_when_registered_at_runtime() {
+  let calls = Calls::default();
+  let mut engine = TestEngine::new();
+  engine.register_object::<Plugin>();
+  engine.start();
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Plugin>("a", 0))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  engine
+    .handle()
+    .register_controller(PluginController::new(1, &calls))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  assert_eq!(
+    calls.take(),
+    ["v1 admit a", "v1 reconcile a", "v1 reconciled a"]
+  );
+}
+
+#[tokio::test(start_paused = true)]
+async fn replacing_drains_the_previous_controller() {
+  let calls = Calls::default();
+  let mut engine = TestEngine::new();
+  engine.register_object::<Plugin>();
+  engine
+    .register_controller(PluginController::new(1, &calls))
+    .unwrap();
+  engine.start();
+  let command = engine.command();
+
+  command
+    .insert_manifest_async(manifest::<Plugin>("a", 5))
+    .await
+    .unwrap();
+  // The engine isn't idle while the reconcile runs, so let it run instead.
+  tokio::time::sleep(Duration::from_millis(10)).await;
+  engine
+    .handle()
+    .replace_controller(PluginController::new(2, &calls))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  // Objects accepted by the previous controller aren't admitted again.
+  assert_eq!(
+    calls.take(),
+    [
+      "v1 admit a",
+      "v1 reconcile a",
+      "v1 reconciled a",
+      "v2 reconcile a",
+      "v2 reconciled a"
+    ]
+  );
+}
+
+#[tokio::test(start_paused = true)]
+async fn unregistering_leaves_objects_unmanaged() {
+  let calls = Calls::default();
+  let mut engine = TestEngine::new();
+  engine.register_object::<Plugin>();
+  engine
+    .register_controller(PluginController::new(1, &calls))
+    .unwrap();
+  engine.start();
+  let command = engine.command();
+
+  engine
+    .handle()
+    .unregister_controller::<Plugin>()
+    .await
+    .unwrap();
+  command
+    .insert_manifest(manifest::<Plugin>("a", 0))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  engine.assert_exists::<Plugin>("a");
+  assert!(calls.take().is_empty());
+  let status = command.watch_status::<Plugin>("a".into()).await.unwrap();
+  assert!(!status.borrow().managed);
+}
+
+#[tokio::test(start_paused = true)]
+async fn replaced_controllers_stop_watching() {
+  let calls = Calls::default();
+  let mut engine = TestEngine::new();
+  engine.register_object::<Plugin>();
+  engine.register_object::<Config>();
+  engine
+    .register_controller_with(PluginController::new(1, &calls), watch_configs())
+    .unwrap();
+  engine.start();
+  let command = engine.command();
+  let handle = engine.handle();
+
+  command
+    .insert_manifest(manifest::<Plugin>("a", 0))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  for version in 2..=3 {
+    handle
+      .replace_controller_with(
+        PluginController::new(version, &calls),
+        watch_configs(),
+      )
+      .await
+      .unwrap();
+    engine.run_until_idle().await;
+  }
+  calls.take();
+
+  command
+    .insert_manifest(manifest::<Config>("config", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(calls.take(), ["v3 reconcile a", "v3 reconciled a"]);
+
+  handle.unregister_controller::<Plugin>().await.unwrap();
+  command
+    .insert_manifest(manifest::<Config>("config", 2))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert!(calls.take().is_empty());
+}
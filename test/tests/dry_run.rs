@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Controller, Error, FieldChange, ObjectDefinition, ObjectManifest, ObjectRef, PlannedChange
+};
+use gusto_test::TestEngine;
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+/// Admitted once the defaults config exists. Version zero is denied, and
+/// admitting version 99 takes a minute.
+struct Service;
+impl ObjectDefinition for Service {
+  type Props = u32;
+}
+
+/// Looked up by service admission.
+struct Config;
+impl ObjectDefinition for Config {
+  type Props = ();
+}
+
+struct ServiceController {
+  calls: Calls,
+  command: Command,
+}
+
+#[async_trait::async_trait]
+impl Controller<Service> for ServiceController {
+  async fn admit_manifest(
+    &self,
+    manifest: ObjectManifest<Service>,
+  ) -> Result<ObjectManifest<Service>> {
+    let name = manifest.name();
+    self.calls.push(format!("admit {name} v{}", manifest.props));
+    self
+      .command
+      .watch_status::<Config>("defaults".into())
+      .await?;
+    match manifest.props {
+      0 => anyhow::bail!("version zero"),
+      99 => tokio::time::sleep(Duration::from_secs(60)).await,
+      _ => {}
+    }
+
+    Ok(manifest)
+  }
+
+  async fn initialize_state(&self, _: &ObjectManifest<Service>) -> Result<()> {
+    Ok(())
+  }
+}
+
+async fn engine(calls: &Calls) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Service>();
+  engine.register_object::<Config>();
+  let controller = ServiceController {
+    calls: calls.clone(),
+    command: engine.command(),
+  };
+  engine.register_controller(controller).unwrap();
+  engine.start();
+
+  engine
+    .command()
+    .insert_manifest(manifest::<Config>("defaults", ()))
+    .await
+    .unwrap();
+  engine
+}
+
+#[tokio::test(start_paused = true)]
+async fn plans_inserts_without_applying_them() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls).await;
+  let command = engine.command();
+
+  let plan = command
+    .dry_run()
+    .insert_manifest(manifest::<Service>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(plan.change, PlannedChange::Create);
+  engine.assert_absent::<Service>("a");
+  assert_eq!(calls.take(), ["admit a v1"]);
+
+  command
+    .insert_manifest(manifest::<Service>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  calls.take();
+
+  let plan = command
+    .dry_run()
+    .insert_manifest(manifest::<Service>("a", 2))
+    .await
+    .unwrap();
+  assert_eq!(plan.change, PlannedChange::Update);
+  let diff = plan.diff.unwrap().iter().cloned().collect::<Vec<_>>();
+  assert_eq!(
+    diff,
+    [FieldChange {
+      path: String::new(),
+      old: Some("1".to_owned()),
+      new: Some("2".to_owned()),
+    }]
+  );
+  assert_eq!(engine.manifest::<Service>("a").unwrap().props, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn fails_dry_runs_denied_admission() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls).await;
+
+  let res = engine
+    .command()
+    .dry_run()
+    .insert_manifest(manifest::<Service>("a", 0))
+    .await;
+  engine.run_until_idle().await;
+
+  assert!(matches!(res, Err(Error::AdmissionDenied { .. })), "{res:?}");
+  engine.assert_absent::<Service>("a");
+}
+
+#[tokio::test(start_paused = true)]
+async fn plans_removals_with_their_cascade() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls).await;
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Service>("a", 1))
+    .await
+    .unwrap();
+  command
+    .insert_owned_manifest(
+      ObjectRef::new::<Service>("a".into()),
+      manifest::<Config>("a-config", ()),
+    )
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  let plan = command
+    .dry_run()
+    .remove_manifest::<Service>("a".into())
+    .await
+    .unwrap();
+  assert_eq!(plan.change, PlannedChange::Remove);
+  assert_eq!(plan.cascade, [ObjectRef::new::<Config>("a-config".into())]);
+  engine.assert_exists::<Service>("a");
+  engine.assert_exists::<Config>("a-config");
+
+  let plan = command
+    .dry_run()
+    .remove_manifest::<Service>("b".into())
+    .await
+    .unwrap();
+  assert_eq!(plan.change, PlannedChange::Absent);
+  assert!(plan.cascade.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn admits_off_the_command_loop() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls).await;
+  let command = engine.command();
+
+  let dry_run = command.clone();
+  let plan = tokio::spawn(async move {
+    dry_run
+      .dry_run()
+      .insert_manifest(manifest::<Service>("slow", 99))
+      .await
+  });
+  tokio::time::sleep(Duration::from_millis(10)).await;
+
+  command
+    .insert_manifest(manifest::<Service>("a", 1))
+    .await
+    .unwrap();
+  assert!(!plan.is_finished());
+
+  engine.run_until_idle().await;
+  let plan = plan.await.unwrap().unwrap();
+  assert_eq!(plan.change, PlannedChange::Create);
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{Command, Controller, ObjectDefinition, ObjectManifest};
+use gusto_test::TestEngine;
+use tokio::time::Instant;
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+/// Seconds after which the object is reconciled again, if any.
+struct Requeue;
+impl ObjectDefinition for Requeue {
+  type Props = Option<u64>;
+}
+
+struct RequeueController {
+  calls: Calls,
+}
+
+#[async_trait::async_trait]
+impl Controller<Requeue> for RequeueController {
+  async fn initialize_state(&self, _: &ObjectManifest<Requeue>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Requeue>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    self.calls.push(format!("reconcile {}", manifest.name()));
+    Ok(manifest.props.map(Duration::from_secs))
+  }
+
+  async fn terminate(
+    &self,
+    manifest: &ObjectManifest<Requeue>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    self.calls.push(format!("terminate {}", manifest.name()));
+    Ok(None)
+  }
+}
+
+/// Sleeps for the given number of seconds on each reconcile.
+struct Sleep;
+impl ObjectDefinition for Sleep {
+  type Props = u64;
+}
+
+struct SleepController;
+
+#[async_trait::async_trait]
+impl Controller<Sleep> for SleepController {
+  async fn initialize_state(&self, _: &ObjectManifest<Sleep>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Sleep>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    tokio::time::sleep(Duration::from_secs(manifest.props)).await;
+    Ok(None)
+  }
+}
+
+fn engine(calls: &Calls) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Requeue>();
+  engine
+    .register_controller(RequeueController {
+      calls: calls.clone(),
+    })
+    .unwrap();
+  engine
+}
+
+#[tokio::test(start_paused = true)]
+async fn reconciles_inserted_objects() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls);
+  let command = engine.command();
+  engine.start();
+
+  command
+    .insert_manifest(manifest::<Requeue>("a", None))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  engine.assert_exists::<Requeue>("a");
+  engine.assert_count::<Requeue>(1);
+  assert_eq!(calls.take(), ["reconcile a"]);
+
+  let status = command.watch_status::<Requeue>("a".into()).await.unwrap();
+  assert_eq!(status.borrow().reconciled_generation, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn requeues_once_the_clock_is_advanced() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls);
+  let command = engine.command();
+  engine.start();
+
+  command
+    .insert_manifest(manifest::<Requeue>("a", Some(10)))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(calls.take(), ["reconcile a"]);
+
+  // Idle while the requeue waits on its timer, which doesn't fire by itself.
+  engine.run_until_idle().await;
+  assert!(calls.take().is_empty());
+
+  engine.advance(Duration::from_secs(5)).await;
+  assert!(calls.take().is_empty());
+
+  engine.advance(Duration::from_secs(5)).await;
+  assert_eq!(calls.take(), ["reconcile a"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn terminates_removed_objects() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls);
+  let command = engine.command();
+  engine.start();
+
+  command
+    .insert_manifest(manifest::<Requeue>("a", None))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  command
+    .remove_manifest::<Requeue>("a".into())
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  engine.assert_absent::<Requeue>("a");
+  assert_eq!(calls.take(), ["reconcile a", "terminate a"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn waits_for_reconciles_in_virtual_time() {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Sleep>();
+  engine.register_controller(SleepController).unwrap();
+  let command = engine.command();
+  engine.start();
+
+  let started = Instant::now();
+  command
+    .insert_manifest(manifest::<Sleep>("a", 60))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  assert!(started.elapsed() >= Duration::from_secs(60));
+  let status = command.watch_status::<Sleep>("a".into()).await.unwrap();
+  assert_eq!(status.borrow().reconciled_generation, 1);
+}
+
+#[tokio::test(start_paused = true)]
+#[should_panic(expected = "engine did not become idle")]
+async fn panics_when_never_idle() {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Sleep>();
+  engine.register_controller(SleepController).unwrap();
+  let command = engine.command();
+  engine.start();
+
+  command
+    .insert_manifest(manifest::<Sleep>("a", 1_000_000))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+}
@@ -0,0 +1,110 @@
+use std::error::Error as _;
+
+use anyhow::{Context, Result};
+use gusto_core::{Controller, Error, ObjectDefinition, ObjectManifest};
+use gusto_test::TestEngine;
+
+use self::common::manifest;
+
+mod common;
+
+/// Admission checks the quota, which a size over ten exceeds.
+struct Volume;
+impl ObjectDefinition for Volume {
+  type Props = u32;
+}
+
+struct Unregistered;
+impl ObjectDefinition for Unregistered {
+  type Props = ();
+}
+
+struct VolumeController;
+
+#[async_trait::async_trait]
+impl Controller<Volume> for VolumeController {
+  async fn admit_manifest(
+    &self,
+    manifest: ObjectManifest<Volume>,
+  ) -> Result<ObjectManifest<Volume>> {
+    if manifest.props > 10 {
+      Err(anyhow::anyhow!("quota exceeded")).context("check quota")?;
+    }
+    Ok(manifest)
+  }
+
+  async fn initialize_state(&self, _: &ObjectManifest<Volume>) -> Result<()> {
+    Ok(())
+  }
+}
+
+fn engine() -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Volume>();
+  engine.register_controller(VolumeController).unwrap();
+  engine.start();
+  engine
+}
+
+#[tokio::test(start_paused = true)]
+async fn reports_unregistered_kinds() {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Volume>();
+  engine.register_controller(VolumeController).unwrap();
+
+  let res = engine.register_controller(VolumeController);
+  assert!(
+    matches!(res, Err(Error::ControllerRegistered(kind)) if kind == Volume::kind())
+  );
+
+  engine.start();
+  let res = engine
+    .command()
+    .insert_manifest(manifest::<Unregistered>("a", ()))
+    .await;
+  match res {
+    Err(e @ Error::KindNotRegistered(_)) => {
+      let kind = Unregistered::kind();
+      assert_eq!(e.to_string(), format!("kind {kind} is not registered"));
+    }
+    res => panic!("expected the kind not to be registered: {res:?}"),
+  }
+}
+
+#[tokio::test(start_paused = true)]
+async fn reports_missing_objects() {
+  let engine = engine();
+
+  let res = engine
+    .command()
+    .patch::<Volume>("a".into(), |size| *size += 1)
+    .await;
+
+  match res {
+    Err(Error::NotFound { kind, name }) => {
+      assert_eq!((kind, name.as_ref()), (Volume::kind(), "a"));
+    }
+    res => panic!("expected the object not to be found: {:?}", res.err()),
+  }
+}
+
+#[tokio::test(start_paused = true)]
+async fn chains_the_sources_of_controller_errors() {
+  let engine = engine();
+
+  let res = engine
+    .command()
+    .dry_run()
+    .insert_manifest(manifest::<Volume>("a", 20))
+    .await;
+
+  let error = res.unwrap_err();
+  assert!(matches!(error, Error::AdmissionDenied { .. }), "{error:?}");
+  assert_eq!(
+    error.to_string(),
+    "manifest 'a' was denied admission: check quota"
+  );
+  let source = error.source().unwrap();
+  assert_eq!(source.to_string(), "check quota");
+  assert_eq!(source.source().unwrap().to_string(), "quota exceeded");
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Controller, Error, EventType, ObjectDefinition, ObjectEvent, ObjectKind, ObjectManifest
+};
+use gusto_engine::Events;
+use gusto_test::TestEngine;
+
+use self::common::manifest;
+
+mod common;
+
+/// Reconciles fail for zero.
+struct Job;
+impl ObjectDefinition for Job {
+  type Props = u32;
+
+  fn kind() -> ObjectKind {
+    "job"
+  }
+}
+
+struct JobController;
+
+#[async_trait::async_trait]
+impl Controller<Job> for JobController {
+  async fn initialize_state(&self, _: &ObjectManifest<Job>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Job>,
+    _: &mut (),
+    command: &Command,
+  ) -> Result<Option<Duration>> {
+    match manifest.props {
+      0 => anyhow::bail!("job failed"),
+      props => {
+        let recorder = command.object_recorder().unwrap();
+        recorder.normal("Started", format!("{props} runs")).await?;
+        Ok(None)
+      }
+    }
+  }
+}
+
+fn engine() -> (TestEngine, Events) {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Job>();
+  engine.register_controller(JobController).unwrap();
+  let events = engine.engine_mut().events();
+  engine.start();
+  (engine, events)
+}
+
+fn summary(events: &[ObjectEvent]) -> Vec<(EventType, &str, &str, u32)> {
+  events
+    .iter()
+    .map(|e| (e.ty, e.reason.as_str(), e.message.as_str(), e.count))
+    .collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn records_events_from_reconciles() {
+  let (mut engine, events) = engine();
+
+  engine
+    .command()
+    .insert_manifest(manifest::<Job>("a", 2))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  let recorded = events.list::<Job>(&"a".into());
+  assert_eq!(
+    summary(&recorded),
+    [(EventType::Normal, "Started", "2 runs", 1)]
+  );
+  assert_eq!(recorded[0].kind, "job");
+  assert_eq!(recorded[0].name, "a".into());
+  assert_eq!(
+    summary(&events.list_kind("job", &"a".into())),
+    summary(&recorded)
+  );
+  assert!(events.list::<Job>(&"b".into()).is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn counts_similar_events_once() {
+  let (mut engine, events) = engine();
+  let command = engine.command();
+  let subscription = events.subscribe();
+
+  command
+    .insert_manifest(manifest::<Job>("a", 2))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  let first = events.list::<Job>(&"a".into()).remove(0);
+
+  engine.advance(Duration::from_secs(5)).await;
+  let recorder = command.recorder::<Job>("a".into());
+  recorder.normal("Started", "2 runs").await.unwrap();
+  recorder.warning("Slow", "took 5s").await.unwrap();
+
+  let recorded = events.list::<Job>(&"a".into());
+  assert_eq!(
+    summary(&recorded),
+    [
+      (EventType::Normal, "Started", "2 runs", 2),
+      (EventType::Warning, "Slow", "took 5s", 1),
+    ]
+  );
+  assert_eq!(recorded[0].first_timestamp, first.first_timestamp);
+
+  // Subscribers see each occurrence, with its running count.
+  let received = subscription.drain().collect::<Vec<_>>();
+  assert_eq!(
+    summary(&received),
+    [
+      (EventType::Normal, "Started", "2 runs", 1),
+      (EventType::Normal, "Started", "2 runs", 2),
+      (EventType::Warning, "Slow", "took 5s", 1),
+    ]
+  );
+}
+
+#[tokio::test(start_paused = true)]
+async fn records_failed_reconciles_as_warnings() {
+  let (mut engine, events) = engine();
+
+  engine
+    .command()
+    .insert_manifest(manifest::<Job>("a", 0))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  engine.advance(Duration::from_secs(1)).await;
+
+  let recorded = events.list::<Job>(&"a".into());
+  assert_eq!(recorded.len(), 1, "{recorded:?}");
+  assert_eq!(recorded[0].ty, EventType::Warning);
+  assert_eq!(recorded[0].reason, "ReconcileFailed");
+  assert!(recorded[0].count >= 2, "{recorded:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn forgets_events_of_removed_objects() {
+  let (mut engine, events) = engine();
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Job>("a", 2))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(events.list::<Job>(&"a".into()).len(), 1);
+
+  command.remove_manifest::<Job>("a".into()).await.unwrap();
+  engine.run_until_idle().await;
+  assert!(events.list::<Job>(&"a".into()).is_empty());
+
+  let res = command
+    .recorder::<Job>("a".into())
+    .normal("Started", "2 runs")
+    .await;
+  assert!(matches!(res, Err(Error::NotFound { .. })), "{res:?}");
+  assert!(events.list::<Job>(&"a".into()).is_empty());
+}
@@ -0,0 +1,205 @@
+use std::{
+  sync::{
+    atomic::{AtomicU32, Ordering}, Arc
+  }, time::Duration
+};
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Condition, Controller, ObjectDefinition, ObjectManifest
+};
+use gusto_test::TestEngine;
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+/// Manifests of version zero are denied, and version five can't tell
+/// whether it should be reconciled.
+struct Version;
+impl ObjectDefinition for Version {
+  type Props = u32;
+}
+
+#[derive(Clone, Default)]
+struct VersionController {
+  calls: Calls,
+  init_failures: Arc<AtomicU32>,
+}
+
+#[async_trait::async_trait]
+impl Controller<Version> for VersionController {
+  async fn admit_manifest(
+    &self,
+    manifest: ObjectManifest<Version>,
+  ) -> Result<ObjectManifest<Version>> {
+    self.calls.push("admit");
+    if manifest.props == 0 {
+      anyhow::bail!("version zero");
+    }
+    Ok(manifest)
+  }
+
+  async fn initialize_state(&self, _: &ObjectManifest<Version>) -> Result<()> {
+    self.calls.push("initialize");
+    let fail = self
+      .init_failures
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+      .is_ok();
+    if fail {
+      anyhow::bail!("not ready");
+    }
+    Ok(())
+  }
+
+  async fn should_reconcile(
+    &self,
+    manifest: &ObjectManifest<Version>,
+  ) -> Result<bool> {
+    if manifest.props == 5 {
+      self.calls.push("should reconcile");
+      anyhow::bail!("version five");
+    }
+    Ok(true)
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Version>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    self.calls.push(format!("reconcile v{}", manifest.props));
+    Ok(None)
+  }
+}
+
+fn engine(controller: &VersionController) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Version>();
+  engine.register_controller(controller.clone()).unwrap();
+  engine.start();
+  engine
+}
+
+async fn accepted(engine: &TestEngine, name: &str) -> Condition {
+  engine
+    .command()
+    .conditions::<Version>(name.into())
+    .await
+    .unwrap()
+    .get(Condition::ACCEPTED)
+    .cloned()
+    .expect("no accepted condition")
+}
+
+#[tokio::test(start_paused = true)]
+async fn retries_failed_initializations() {
+  let controller = VersionController::default();
+  controller.init_failures.store(2, Ordering::SeqCst);
+  let mut engine = engine(&controller);
+
+  engine
+    .command()
+    .insert_manifest(manifest::<Version>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  let condition = accepted(&engine, "a").await;
+  assert!(condition.is_false());
+  assert_eq!(condition.reason, "InitializeFailed");
+  assert_eq!(controller.calls.take(), ["admit", "initialize"]);
+
+  engine.advance(Duration::from_secs(1)).await;
+  engine.advance(Duration::from_secs(1)).await;
+
+  assert!(accepted(&engine, "a").await.is_true());
+  assert_eq!(
+    controller.calls.take(),
+    ["admit", "initialize", "admit", "initialize", "reconcile v1"]
+  );
+}
+
+#[tokio::test(start_paused = true)]
+async fn waits_for_an_update_after_a_denial() {
+  let controller = VersionController::default();
+  let mut engine = engine(&controller);
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Version>("a", 0))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  engine.advance(Duration::from_secs(600)).await;
+
+  let condition = accepted(&engine, "a").await;
+  assert!(condition.is_false());
+  assert_eq!(condition.reason, "AdmissionDenied");
+  assert_eq!(controller.calls.take(), ["admit"]);
+
+  command
+    .insert_manifest(manifest::<Version>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  assert!(accepted(&engine, "a").await.is_true());
+  assert_eq!(
+    controller.calls.take(),
+    ["admit", "initialize", "reconcile v1"]
+  );
+}
+
+#[tokio::test(start_paused = true)]
+async fn reports_the_step_that_failed() {
+  let controller = VersionController::default();
+  let mut engine = engine(&controller);
+
+  engine
+    .command()
+    .insert_manifest(manifest::<Version>("a", 5))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  let condition = accepted(&engine, "a").await;
+  assert!(condition.is_false());
+  assert_eq!(condition.reason, "ShouldReconcileFailed");
+  assert_eq!(condition.message, "version five");
+}
+
+#[tokio::test(start_paused = true)]
+async fn ignores_retries_of_healed_failures() {
+  let controller = VersionController::default();
+  controller.init_failures.store(1, Ordering::SeqCst);
+  let mut engine = engine(&controller);
+  let command = engine.command();
+
+  // Fails at 0ms, retried at 100ms.
+  command
+    .insert_manifest(manifest::<Version>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  // Heals, then fails again at 50ms, retried at 150ms.
+  command
+    .insert_manifest(manifest::<Version>("a", 2))
+    .await
+    .unwrap();
+  engine.advance(Duration::from_millis(50)).await;
+  command
+    .insert_manifest(manifest::<Version>("a", 5))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(controller.calls.count("should reconcile"), 1);
+
+  engine.advance(Duration::from_millis(60)).await;
+  assert_eq!(controller.calls.count("should reconcile"), 1);
+
+  engine.advance(Duration::from_millis(50)).await;
+  assert_eq!(controller.calls.count("should reconcile"), 2);
+}
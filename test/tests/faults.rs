@@ -0,0 +1,160 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{Command, Controller, ObjectDefinition, ObjectManifest};
+use gusto_engine::{FaultConfig, Faults};
+use gusto_test::TestEngine;
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+/// Reconciles of version zero wait until cancelled.
+struct Version;
+impl ObjectDefinition for Version {
+  type Props = u32;
+}
+
+struct VersionController {
+  calls: Calls,
+}
+
+#[async_trait::async_trait]
+impl Controller<Version> for VersionController {
+  async fn initialize_state(
+    &self,
+    manifest: &ObjectManifest<Version>,
+  ) -> Result<()> {
+    self.calls.push(format!("initialize {}", manifest.name()));
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Version>,
+    _: &mut (),
+    command: &Command,
+  ) -> Result<Option<Duration>> {
+    let name = manifest.name();
+    if manifest.props == 0 {
+      command.cancellation().cancelled().await;
+      self.calls.push(format!("cancel {name}"));
+    } else {
+      self
+        .calls
+        .push(format!("reconcile {name} v{}", manifest.props));
+    }
+
+    Ok(None)
+  }
+}
+
+fn engine(calls: &Calls) -> (TestEngine, Faults) {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Version>();
+  engine
+    .register_controller(VersionController {
+      calls: calls.clone(),
+    })
+    .unwrap();
+  let faults = engine.engine_mut().faults();
+  (engine, faults)
+}
+
+#[tokio::test(start_paused = true)]
+async fn restart_rebuilds_objects_from_the_store() {
+  let calls = Calls::default();
+  let (mut engine, faults) = engine(&calls);
+  let command = engine.command();
+  engine.start();
+
+  command
+    .insert_manifest(manifest::<Version>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(calls.take(), ["initialize a", "reconcile a v1"]);
+
+  faults.restart();
+  engine.run_until_idle().await;
+  assert_eq!(calls.take(), ["initialize a", "reconcile a v1"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn restart_cancels_reconciles_in_flight() {
+  let calls = Calls::default();
+  let (mut engine, faults) = engine(&calls);
+  let command = engine.command();
+  engine.start();
+
+  command
+    .insert_manifest_async(manifest::<Version>("a", 0))
+    .await
+    .unwrap();
+  // The engine isn't idle while the reconcile waits, so let it run instead.
+  tokio::time::sleep(Duration::from_millis(10)).await;
+  assert_eq!(calls.take(), ["initialize a"]);
+
+  faults.restart();
+  tokio::time::sleep(Duration::from_millis(10)).await;
+  assert_eq!(calls.take(), ["cancel a", "initialize a"]);
+
+  command
+    .insert_manifest(manifest::<Version>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(calls.take(), ["cancel a", "reconcile a v1"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn delayed_events_keep_their_order() {
+  let calls = Calls::default();
+  let (mut engine, faults) = engine(&calls);
+  let command = engine.command();
+  engine.start();
+
+  faults.enable(FaultConfig {
+    seed: 7,
+    max_event_delay: Duration::from_secs(1),
+    ..Default::default()
+  });
+  for version in 1..=5 {
+    command
+      .insert_manifest(manifest::<Version>("a", version))
+      .await
+      .unwrap();
+  }
+  engine.advance(Duration::from_secs(5)).await;
+
+  let calls = calls.take();
+  assert_eq!(calls.first().map(String::as_str), Some("initialize a"));
+  assert_eq!(calls.last().map(String::as_str), Some("reconcile a v5"));
+  let status = command.watch_status::<Version>("a".into()).await.unwrap();
+  assert_eq!(status.borrow().reconciled_generation, 5);
+}
+
+#[tokio::test(start_paused = true)]
+async fn failed_writes_are_reported() {
+  let calls = Calls::default();
+  let (mut engine, faults) = engine(&calls);
+  let command = engine.command();
+  engine.start();
+
+  faults.enable(FaultConfig {
+    write_failure_rate: 1.0,
+    ..Default::default()
+  });
+  let res = command.insert_manifest(manifest::<Version>("a", 1)).await;
+  assert!(res.is_err());
+  engine.run_until_idle().await;
+  engine.assert_absent::<Version>("a");
+
+  faults.disable();
+  command
+    .insert_manifest(manifest::<Version>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(calls.take(), ["initialize a", "reconcile a v1"]);
+}
@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Controller, Error, ObjectDefinition, ObjectKind, ObjectManifest
+};
+use gusto_engine::ObjectOptions;
+use gusto_test::TestEngine;
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+/// Pods are indexed by the nodes they run on.
+struct Pod;
+impl ObjectDefinition for Pod {
+  type Props = Vec<String>;
+
+  fn kind() -> ObjectKind {
+    "pod"
+  }
+}
+
+/// Nodes list their pods through the index on every reconcile.
+struct Node;
+impl ObjectDefinition for Node {
+  type Props = ();
+
+  fn kind() -> ObjectKind {
+    "node"
+  }
+}
+
+struct NodeController {
+  calls: Calls,
+}
+
+#[async_trait::async_trait]
+impl Controller<Node> for NodeController {
+  async fn initialize_state(&self, _: &ObjectManifest<Node>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Node>,
+    _: &mut (),
+    command: &Command,
+  ) -> Result<Option<Duration>> {
+    let pods = command.lister::<Pod>()?.by_index("node", manifest.name())?;
+    let names = pods.iter().map(|pod| pod.name().to_string());
+    let names = names.collect::<Vec<_>>().join(",");
+    self.calls.push(format!("{} [{names}]", manifest.name()));
+    Ok(None)
+  }
+}
+
+fn engine(calls: &Calls) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object_with::<Pod>(
+    ObjectOptions::new()
+      .index("node", |pod: &ObjectManifest<Pod>| pod.props.clone()),
+  );
+  engine.register_object::<Node>();
+  engine
+    .register_controller(NodeController {
+      calls: calls.clone(),
+    })
+    .unwrap();
+  engine.start();
+  engine
+}
+
+fn pod(name: &str, nodes: &[&str]) -> ObjectManifest<Pod> {
+  manifest(name, nodes.iter().map(|node| node.to_string()).collect())
+}
+
+fn names(pods: Vec<ObjectManifest<Pod>>) -> Vec<String> {
+  pods.iter().map(|pod| pod.name().to_string()).collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn lists_by_index_from_reconciles() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls);
+  let command = engine.command();
+
+  command.insert_manifest(pod("x", &["a"])).await.unwrap();
+  command
+    .insert_manifest(pod("y", &["a", "b"]))
+    .await
+    .unwrap();
+  command.insert_manifest(pod("z", &["b"])).await.unwrap();
+  for node in ["a", "b", "c"] {
+    command
+      .insert_manifest(manifest::<Node>(node, ()))
+      .await
+      .unwrap();
+  }
+  engine.run_until_idle().await;
+
+  let mut reconciled = calls.take();
+  reconciled.sort();
+  assert_eq!(reconciled, ["a [x,y]", "b [y,z]", "c []"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn reindexes_updated_and_removed_objects() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls);
+  let command = engine.command();
+  let lister = command.lister::<Pod>().unwrap();
+
+  command.insert_manifest(pod("x", &["a"])).await.unwrap();
+  command.insert_manifest(pod("y", &["a"])).await.unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(names(lister.by_index("node", "a").unwrap()), ["x", "y"]);
+
+  command.insert_manifest(pod("x", &["b"])).await.unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(names(lister.by_index("node", "a").unwrap()), ["y"]);
+  assert_eq!(names(lister.by_index("node", "b").unwrap()), ["x"]);
+
+  command.remove_manifest::<Pod>("y".into()).await.unwrap();
+  engine.run_until_idle().await;
+  assert!(lister.by_index("node", "a").unwrap().is_empty());
+
+  assert_eq!(names(lister.list()), ["x"]);
+  assert!(lister.get(&"x".into()).is_some());
+  assert!(lister.get(&"y".into()).is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn indexes_objects_stored_before_the_index() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls);
+  let command = engine.command();
+
+  command.insert_manifest(pod("x", &["a"])).await.unwrap();
+  engine.run_until_idle().await;
+
+  let store = engine.store::<Pod>();
+  store.add_index("first", |pod| pod.props.iter().take(1).cloned().collect());
+  assert_eq!(names(store.by_index("first", "a").unwrap()), ["x"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn rejects_unknown_indexes_and_kinds() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls);
+  let command = engine.command();
+
+  command.insert_manifest(pod("x", &["a"])).await.unwrap();
+  engine.run_until_idle().await;
+
+  let res = command.lister::<Pod>().unwrap().by_index("zone", "a");
+  match res {
+    Err(Error::IndexNotRegistered { kind, index }) => {
+      assert_eq!(kind, "pod");
+      assert_eq!(index, "zone");
+    }
+    res => panic!("expected IndexNotRegistered, got {:?}", res.err()),
+  }
+
+  // Kinds without an index option have none registered.
+  let res = command.lister::<Node>().unwrap().by_index("node", "a");
+  assert!(matches!(res, Err(Error::IndexNotRegistered { .. })));
+
+  struct Unknown;
+  impl ObjectDefinition for Unknown {
+    type Props = ();
+  }
+  let res = command.lister::<Unknown>();
+  assert!(matches!(res, Err(Error::KindNotRegistered(_))));
+}
@@ -0,0 +1,200 @@
+use std::{
+  sync::{
+    atomic::{AtomicUsize, Ordering}, Arc
+  }, time::Duration
+};
+
+use anyhow::Result;
+use gusto_core::{Command, Controller, ObjectDefinition, ObjectManifest};
+use gusto_engine::{ControllerOptions, RateLimit};
+use gusto_test::TestEngine;
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+/// Milliseconds each reconcile takes.
+struct Job;
+impl ObjectDefinition for Job {
+  type Props = u64;
+}
+
+#[derive(Clone, Default)]
+struct JobController {
+  calls: Calls,
+  running: Arc<AtomicUsize>,
+  max_running: Arc<AtomicUsize>,
+  starts: Arc<Mutex<Vec<Instant>>>,
+}
+
+#[async_trait::async_trait]
+impl Controller<Job> for JobController {
+  async fn initialize_state(&self, _: &ObjectManifest<Job>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Job>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    self.calls.push(manifest.name().to_string());
+    self.starts.lock().push(Instant::now());
+    let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+    self.max_running.fetch_max(running, Ordering::SeqCst);
+
+    tokio::time::sleep(Duration::from_millis(manifest.props)).await;
+    self.running.fetch_sub(1, Ordering::SeqCst);
+
+    Ok(None)
+  }
+}
+
+impl JobController {
+  /// Returns the time between consecutive reconciles.
+  fn gaps(&self) -> Vec<Duration> {
+    let starts = self.starts.lock();
+    starts.windows(2).map(|w| w[1] - w[0]).collect()
+  }
+}
+
+fn engine(
+  controller: &JobController,
+  options: ControllerOptions<Job>,
+) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Job>();
+  engine
+    .register_controller_with(controller.clone(), options)
+    .unwrap();
+  engine.start();
+  engine
+}
+
+async fn insert_jobs(engine: &mut TestEngine, count: usize) {
+  let command = engine.command();
+  for i in 0..count {
+    command
+      .insert_manifest(manifest::<Job>(&format!("job-{i}"), 0))
+      .await
+      .unwrap();
+  }
+}
+
+#[tokio::test(start_paused = true)]
+async fn limits_concurrent_reconciles() {
+  let controller = JobController::default();
+  let options = ControllerOptions::default().max_concurrent_reconciles(2);
+  let mut engine = engine(&controller, options);
+  let command = engine.command();
+
+  let started = Instant::now();
+  for i in 0..10 {
+    command
+      .insert_manifest(manifest::<Job>(&format!("job-{i}"), 1000))
+      .await
+      .unwrap();
+  }
+  engine.run_until_idle().await;
+
+  assert_eq!(controller.max_running.load(Ordering::SeqCst), 2);
+  assert!(started.elapsed() >= Duration::from_secs(5));
+
+  // Waiting objects take their turn in FIFO order.
+  let expected = (0..10).map(|i| format!("job-{i}")).collect::<Vec<_>>();
+  assert_eq!(controller.calls.take(), expected);
+}
+
+#[tokio::test(start_paused = true)]
+async fn runs_reconciles_concurrently_by_default() {
+  let controller = JobController::default();
+  let mut engine = engine(&controller, ControllerOptions::default());
+  let command = engine.command();
+
+  let started = Instant::now();
+  for i in 0..10 {
+    command
+      .insert_manifest(manifest::<Job>(&format!("job-{i}"), 1000))
+      .await
+      .unwrap();
+  }
+  engine.run_until_idle().await;
+
+  assert_eq!(controller.max_running.load(Ordering::SeqCst), 10);
+  assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test(start_paused = true)]
+async fn limits_the_rate_of_reconciles() {
+  let controller = JobController::default();
+  let options = ControllerOptions::default().rate_limit(RateLimit::new(1.0, 1));
+  let mut engine = engine(&controller, options);
+
+  insert_jobs(&mut engine, 3).await;
+  engine.run_until_idle().await;
+  assert_eq!(controller.calls.take(), ["job-0"]);
+
+  // Rate limited reconciles wait on a timer, like requeues.
+  for _ in 0..3 {
+    engine.advance(Duration::from_secs(1)).await;
+  }
+  assert_eq!(controller.calls.take(), ["job-1", "job-2"]);
+  for gap in controller.gaps() {
+    assert!(gap >= Duration::from_millis(900), "{gap:?}");
+  }
+}
+
+#[tokio::test(start_paused = true)]
+async fn limits_the_rate_of_reconciles_per_object() {
+  let controller = JobController::default();
+  let options =
+    ControllerOptions::default().object_rate_limit(RateLimit::new(0.1, 1));
+  let mut engine = engine(&controller, options);
+  let command = engine.command();
+
+  insert_jobs(&mut engine, 2).await;
+  engine.run_until_idle().await;
+  assert_eq!(controller.calls.take(), ["job-0", "job-1"]);
+
+  command
+    .insert_manifest(manifest::<Job>("job-0", 1))
+    .await
+    .unwrap();
+  engine.advance(Duration::from_secs(5)).await;
+  assert!(controller.calls.take().is_empty());
+
+  engine.advance(Duration::from_secs(5)).await;
+  assert_eq!(controller.calls.take(), ["job-0"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn limits_the_rate_of_reconciles_across_controllers() {
+  let controller = JobController::default();
+  let mut engine = TestEngine::new();
+  engine.register_object::<Job>();
+  engine.register_controller(controller.clone()).unwrap();
+  engine.engine_mut().set_rate_limit(RateLimit::new(2.0, 2));
+  engine.start();
+
+  insert_jobs(&mut engine, 4).await;
+  engine.run_until_idle().await;
+  assert_eq!(controller.calls.take(), ["job-0", "job-1"]);
+
+  engine.advance(Duration::from_secs(1)).await;
+  assert_eq!(controller.calls.take(), ["job-2", "job-3"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn zero_rate_is_unlimited() {
+  let controller = JobController::default();
+  let options = ControllerOptions::default().rate_limit(RateLimit::new(0.0, 1));
+  let mut engine = engine(&controller, options);
+
+  insert_jobs(&mut engine, 5).await;
+  engine.run_until_idle().await;
+
+  assert_eq!(controller.calls.take().len(), 5);
+}
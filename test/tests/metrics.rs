@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Controller, ObjectDefinition, ObjectKind, ObjectManifest
+};
+use gusto_engine::Metrics;
+use gusto_test::TestEngine;
+use metrics_util::{
+  debugging::{DebugValue, DebuggingRecorder}, CompositeKey, MetricKind
+};
+
+use self::common::manifest;
+
+mod common;
+
+/// Reconciles take as many seconds as the props, and fail for zero.
+struct Job;
+impl ObjectDefinition for Job {
+  type Props = u64;
+
+  fn kind() -> ObjectKind {
+    "job"
+  }
+}
+
+/// Same as jobs, only reported to the installed recorder.
+struct Report;
+impl ObjectDefinition for Report {
+  type Props = u64;
+
+  fn kind() -> ObjectKind {
+    "report"
+  }
+}
+
+struct SleepController;
+
+#[async_trait::async_trait]
+impl<O> Controller<O> for SleepController
+where
+  O: ObjectDefinition<Props = u64, State = ()>,
+{
+  async fn initialize_state(&self, _: &ObjectManifest<O>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<O>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    match manifest.props {
+      0 => anyhow::bail!("reconcile failed"),
+      secs => tokio::time::sleep(Duration::from_secs(secs)).await,
+    }
+    Ok(None)
+  }
+}
+
+fn engine<O>() -> (TestEngine, Metrics)
+where
+  O: ObjectDefinition<Props = u64, State = ()>,
+{
+  let mut engine = TestEngine::new();
+  engine.register_object::<O>();
+  engine.register_controller::<O>(SleepController).unwrap();
+  let metrics = engine.engine_mut().metrics();
+  engine.start();
+  (engine, metrics)
+}
+
+fn lines<'a>(metrics: &'a str, prefix: &str) -> Vec<&'a str> {
+  metrics
+    .lines()
+    .filter(|line| line.starts_with(prefix))
+    .collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn renders_commands_and_reconciles() {
+  let (mut engine, metrics) = engine::<Job>();
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Job>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  // Nothing runs long enough for the retry to fire.
+  command
+    .insert_manifest(manifest::<Job>("b", 0))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  command.remove_manifest::<Job>("c".into()).await.unwrap();
+
+  let metrics = metrics.render();
+  assert_eq!(
+    lines(&metrics, "gusto_commands_total"),
+    [
+      "gusto_commands_total{action=\"InsertManifest\",kind=\"job\"} 2",
+      "gusto_commands_total{action=\"RecordEvent\",kind=\"job\"} 1",
+      "gusto_commands_total{action=\"RemoveManifest\",kind=\"job\"} 1",
+    ]
+  );
+  assert_eq!(
+    lines(&metrics, "gusto_reconcile_duration_seconds_count"),
+    ["gusto_reconcile_duration_seconds_count{kind=\"job\"} 2"]
+  );
+  assert_eq!(
+    lines(&metrics, "gusto_reconcile_errors_total"),
+    ["gusto_reconcile_errors_total{kind=\"job\"} 1"]
+  );
+  assert_eq!(
+    lines(&metrics, "gusto_reconciles_in_flight"),
+    ["gusto_reconciles_in_flight{kind=\"job\"} 0"]
+  );
+}
+
+#[tokio::test(start_paused = true)]
+async fn reports_the_depths_of_replaced_controllers_once() {
+  let (mut engine, metrics) = engine::<Job>();
+  let requeue = "gusto_channel_depth{channel=\"requeue\",kind=\"job\"}";
+
+  engine
+    .command()
+    .insert_manifest_async(manifest::<Job>("a", 5))
+    .await
+    .unwrap();
+  tokio::time::sleep(Duration::from_millis(10)).await;
+
+  // The previous controller drains the running reconcile meanwhile.
+  let handle = engine.handle();
+  let replaced = tokio::spawn(async move {
+    handle.replace_controller::<Job>(SleepController).await
+  });
+  tokio::time::sleep(Duration::from_millis(10)).await;
+  assert!(!replaced.is_finished());
+  assert_eq!(lines(&metrics.render(), requeue).len(), 1);
+
+  engine.run_until_idle().await;
+  replaced.await.unwrap().unwrap();
+  assert_eq!(lines(&metrics.render(), requeue).len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn emits_through_the_installed_recorder() {
+  let recorder = DebuggingRecorder::new();
+  let snapshotter = recorder.snapshotter();
+  recorder.install().unwrap();
+  Metrics::describe();
+
+  let (mut engine, metrics) = engine::<Report>();
+  engine
+    .command()
+    .insert_manifest(manifest::<Report>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  metrics.record_depths();
+
+  let reported = snapshotter
+    .snapshot()
+    .into_vec()
+    .into_iter()
+    .filter(|(key, ..)| {
+      key
+        .key()
+        .labels()
+        .any(|l| l.key() == "kind" && l.value() == "report")
+    })
+    .map(|(key, _, help, value)| (describe(&key), help.is_some(), value))
+    .collect::<Vec<_>>();
+
+  let value = |name: &str| {
+    let (_, described, value) = reported
+      .iter()
+      .find(|(key, ..)| key == name)
+      .unwrap_or_else(|| panic!("{name} was not reported: {reported:?}"));
+    assert!(*described, "{name} has no description");
+    value
+  };
+  assert_eq!(
+    value("counter gusto_commands_total action=InsertManifest kind=report"),
+    &DebugValue::Counter(1)
+  );
+  assert!(matches!(
+    value("histogram gusto_reconcile_duration_seconds kind=report"),
+    DebugValue::Histogram(durations) if durations.len() == 1
+  ));
+  assert_eq!(
+    value("gauge gusto_reconciles_in_flight kind=report"),
+    &DebugValue::Gauge(0.0.into())
+  );
+  assert_eq!(
+    value("gauge gusto_channel_depth channel=requeue kind=report"),
+    &DebugValue::Gauge(0.0.into())
+  );
+}
+
+fn describe(key: &CompositeKey) -> String {
+  let kind = match key.kind() {
+    MetricKind::Counter => "counter",
+    MetricKind::Gauge => "gauge",
+    MetricKind::Histogram => "histogram",
+  };
+  let labels = key
+    .key()
+    .labels()
+    .map(|l| format!(" {}={}", l.key(), l.value()))
+    .collect::<String>();
+
+  format!("{kind} {}{labels}", key.key().name())
+}
@@ -0,0 +1,163 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Condition, ConditionStatus, Controller, Error, ObjectDefinition, ObjectKind, ObjectManifest, ObjectRef
+};
+use gusto_test::{MockAction, MockCommand};
+
+use self::common::manifest;
+
+mod common;
+
+/// Paths of the pages to publish.
+struct Website;
+impl ObjectDefinition for Website {
+  type Props = Vec<&'static str>;
+
+  fn kind() -> ObjectKind {
+    "website"
+  }
+}
+
+struct Page;
+impl ObjectDefinition for Page {
+  type Props = ();
+
+  fn kind() -> ObjectKind {
+    "page"
+  }
+}
+
+/// Publishes the pages of websites, then marks them ready.
+struct WebsiteController;
+
+#[async_trait::async_trait]
+impl Controller<Website> for WebsiteController {
+  async fn initialize_state(&self, _: &ObjectManifest<Website>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    website: &ObjectManifest<Website>,
+    _: &mut (),
+    command: &Command,
+  ) -> Result<Option<Duration>> {
+    let name = website.name().to_owned();
+    let owner = ObjectRef::new::<Website>(name.clone());
+    for path in &website.props {
+      command
+        .insert_owned_manifest(owner.clone(), manifest::<Page>(path, ()))
+        .await?;
+    }
+
+    let ready = Condition::new(Condition::READY, ConditionStatus::True);
+    command
+      .set_condition::<Website>(name.clone(), ready)
+      .await?;
+    command
+      .recorder::<Website>(name)
+      .normal("Published", format!("{} pages", website.props.len()))
+      .await?;
+
+    Ok(None)
+  }
+}
+
+/// Returns a mock knowing about the website, without recording it.
+async fn mock(website: &ObjectManifest<Website>) -> MockCommand {
+  let mock = MockCommand::new();
+  mock
+    .command()
+    .insert_manifest(manifest::<Website>(website.name(), vec![]))
+    .await
+    .unwrap();
+  mock.clear();
+  mock
+}
+
+async fn reconcile(
+  mock: &MockCommand,
+  website: &ObjectManifest<Website>,
+) -> Result<Option<Duration>> {
+  WebsiteController
+    .reconcile(website, &mut (), &mock.command())
+    .await
+}
+
+#[tokio::test]
+async fn records_what_a_controller_does() {
+  let website = manifest::<Website>("site", vec!["home", "about"]);
+  let mock = mock(&website).await;
+
+  reconcile(&mock, &website).await.unwrap();
+
+  mock.assert_golden(
+    "
+    insert page home owned by website site
+    insert page about owned by website site
+    condition website site Ready=true
+    event website site normal Published: 2 pages
+    ",
+  );
+  let owned = mock
+    .inserted_owned::<Page>()
+    .into_iter()
+    .map(|(owner, page)| (owner.name, page.name().to_owned()))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    owned,
+    [
+      ("site".into(), "home".into()),
+      ("site".into(), "about".into())
+    ]
+  );
+}
+
+#[tokio::test]
+async fn fails_the_next_command() {
+  let website = manifest::<Website>("site", vec!["home", "about"]);
+  let mock = mock(&website).await;
+
+  mock.fail_next(Error::InjectedFault("write"));
+  let res = reconcile(&mock, &website).await;
+
+  let error = res.unwrap_err().downcast::<Error>().unwrap();
+  assert!(matches!(error, Error::InjectedFault(_)), "{error:?}");
+  mock.assert_golden("insert page home owned by website site");
+
+  // Only the next command fails.
+  mock.clear();
+  reconcile(&mock, &website).await.unwrap();
+  assert_eq!(mock.inserted::<Page>().len(), 2);
+}
+
+#[tokio::test]
+async fn responds_with_scripted_results() {
+  let website = manifest::<Website>("site", vec!["home", "admin", "about"]);
+  let mock = mock(&website).await;
+
+  mock.respond_with(|action| {
+    match action {
+      MockAction::Insert { name, .. } if **name == *"admin" => {
+        Err(Error::Conflict {
+          name: name.to_owned(),
+          reason: "reserved".to_owned(),
+        })
+      }
+      _ => Ok(()),
+    }
+  });
+  let res = reconcile(&mock, &website).await;
+
+  let error = res.unwrap_err().downcast::<Error>().unwrap();
+  assert!(matches!(error, Error::Conflict { .. }), "{error:?}");
+  // The rejected insert is recorded, and the reconcile stops there.
+  mock.assert_golden(
+    "
+    insert page home owned by website site
+    insert page admin owned by website site
+    ",
+  );
+}
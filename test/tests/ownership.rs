@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Controller, Error, ObjectDefinition, ObjectManifest, ObjectRef
+};
+use gusto_engine::ControllerOptions;
+use gusto_test::TestEngine;
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+struct Parent;
+impl ObjectDefinition for Parent {
+  type Props = ();
+}
+
+/// Has no controller.
+struct Child;
+impl ObjectDefinition for Child {
+  type Props = u32;
+}
+
+struct ParentController {
+  calls: Calls,
+}
+
+#[async_trait::async_trait]
+impl Controller<Parent> for ParentController {
+  async fn initialize_state(&self, _: &ObjectManifest<Parent>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Parent>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    self.calls.push(format!("reconcile {}", manifest.name()));
+    Ok(None)
+  }
+}
+
+async fn engine(calls: &Calls) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Parent>();
+  engine.register_object::<Child>();
+  engine
+    .register_controller_with(
+      ParentController {
+        calls: calls.clone(),
+      },
+      ControllerOptions::default().owns::<Child>(),
+    )
+    .unwrap();
+  engine.start();
+
+  for name in ["p", "q"] {
+    engine
+      .command()
+      .insert_manifest(manifest::<Parent>(name, ()))
+      .await
+      .unwrap();
+  }
+  engine.run_until_idle().await;
+  calls.take();
+  engine
+}
+
+async fn insert_owned(
+  engine: &TestEngine,
+  owner: &str,
+  name: &str,
+  props: u32,
+) -> gusto_core::Result<()> {
+  engine
+    .command()
+    .insert_owned_manifest(
+      ObjectRef::new::<Parent>(owner.into()),
+      manifest::<Child>(name, props),
+    )
+    .await
+}
+
+#[tokio::test(start_paused = true)]
+async fn reowning_by_the_same_owner_updates_the_object() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls).await;
+
+  insert_owned(&engine, "p", "c", 1).await.unwrap();
+  insert_owned(&engine, "p", "c", 2).await.unwrap();
+  engine.run_until_idle().await;
+
+  engine.assert_owned::<Parent, Child>("p", "c");
+  assert_eq!(engine.manifest::<Child>("c").unwrap().props, 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn denies_objects_owned_by_another() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls).await;
+
+  insert_owned(&engine, "p", "c", 1).await.unwrap();
+  let res = insert_owned(&engine, "q", "c", 2).await;
+  engine.run_until_idle().await;
+
+  match res {
+    Err(Error::AlreadyOwned { owner, name }) => {
+      assert_eq!((owner.as_ref(), name.as_ref()), ("p", "c"));
+    }
+    res => panic!("expected the object to be owned already: {res:?}"),
+  }
+  engine.assert_not_owned::<Parent, Child>("q", "c");
+  assert_eq!(engine.manifest::<Child>("c").unwrap().props, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn releases_objects_removed_directly() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls).await;
+  let command = engine.command();
+
+  insert_owned(&engine, "p", "c", 1).await.unwrap();
+  command.remove_manifest::<Child>("c".into()).await.unwrap();
+  engine.run_until_idle().await;
+  engine.assert_not_owned::<Parent, Child>("p", "c");
+  assert!(engine.snapshot().owners.is_empty());
+
+  insert_owned(&engine, "q", "c", 2).await.unwrap();
+  command.remove_manifest::<Parent>("p".into()).await.unwrap();
+  engine.run_until_idle().await;
+  engine.assert_exists::<Child>("c");
+  engine.assert_owned::<Parent, Child>("q", "c");
+}
+
+#[tokio::test(start_paused = true)]
+async fn reconciles_owners_when_what_they_own_changes() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls).await;
+  let command = engine.command();
+
+  insert_owned(&engine, "p", "c", 1).await.unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(calls.take(), ["reconcile p"]);
+
+  command
+    .patch::<Child>("c".into(), |props| *props = 2)
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(calls.take(), ["reconcile p"]);
+
+  command.remove_manifest::<Child>("c".into()).await.unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(calls.take(), ["reconcile p"]);
+
+  // Recreated without an owner, it's no longer the owner's business.
+  command
+    .insert_manifest(manifest::<Child>("c", 3))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert!(calls.take().is_empty());
+}
@@ -0,0 +1,258 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Controller, Error, ObjectDefinition, ObjectManifest
+};
+use gusto_test::TestEngine;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+/// Admission fills in the default image.
+struct Deployment;
+impl ObjectDefinition for Deployment {
+  type Props = Spec;
+
+  fn same_props(old: &Spec, new: &Spec) -> bool {
+    old == new
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+struct Spec {
+  image: String,
+  replicas: u32,
+}
+
+fn spec(image: &str, replicas: u32) -> Spec {
+  Spec {
+    image: image.to_owned(),
+    replicas,
+  }
+}
+
+struct DeploymentController {
+  calls: Calls,
+}
+
+#[async_trait::async_trait]
+impl Controller<Deployment> for DeploymentController {
+  async fn admit_manifest(
+    &self,
+    mut manifest: ObjectManifest<Deployment>,
+  ) -> Result<ObjectManifest<Deployment>> {
+    if manifest.props.image.is_empty() {
+      manifest.props.image = "default".to_owned();
+    }
+    Ok(manifest)
+  }
+
+  async fn initialize_state(
+    &self,
+    _: &ObjectManifest<Deployment>,
+  ) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Deployment>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    let Spec { image, replicas } = &manifest.props;
+    self.calls.push(format!("reconcile {image} x{replicas}"));
+    Ok(None)
+  }
+}
+
+async fn engine(calls: &Calls, props: Spec) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Deployment>();
+  engine
+    .register_controller(DeploymentController {
+      calls: calls.clone(),
+    })
+    .unwrap();
+  engine.start();
+
+  engine
+    .command()
+    .insert_manifest(manifest::<Deployment>("a", props))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  calls.take();
+  engine
+}
+
+#[tokio::test(start_paused = true)]
+async fn patches_props() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls, spec("app", 1)).await;
+
+  let manifest = engine
+    .command()
+    .patch::<Deployment>("a".into(), |props| props.replicas = 3)
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  assert_eq!(manifest.props, spec("app", 3));
+  assert_eq!(manifest.meta.generation, 2);
+  assert_eq!(calls.take(), ["reconcile app x3"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn leaves_failed_patches_untouched() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls, spec("app", 1)).await;
+
+  let res = engine
+    .command()
+    .try_patch::<Deployment>("a".into(), |props| {
+      props.replicas = 3;
+      Err(Error::InjectedFault("patch"))
+    })
+    .await;
+  engine.run_until_idle().await;
+
+  assert!(
+    matches!(res, Err(Error::InjectedFault(_))),
+    "{:?}",
+    res.err()
+  );
+  let manifest = engine.manifest::<Deployment>("a").unwrap();
+  assert_eq!(manifest.props, spec("app", 1));
+  assert_eq!(manifest.meta.generation, 1);
+  assert!(calls.take().is_empty());
+
+  let res = engine
+    .command()
+    .patch::<Deployment>("b".into(), |props| props.replicas = 3)
+    .await;
+  assert!(
+    matches!(res, Err(Error::NotFound { .. })),
+    "{:?}",
+    res.err()
+  );
+}
+
+#[tokio::test(start_paused = true)]
+async fn merge_patches_props() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls, spec("app", 1)).await;
+
+  let manifest = engine
+    .command()
+    .merge_patch::<Deployment>("a".into(), json!({ "replicas": 2 }))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  assert_eq!(manifest.props, spec("app", 2));
+  assert_eq!(calls.take(), ["reconcile app x2"]);
+
+  let res = engine
+    .command()
+    .merge_patch::<Deployment>("a".into(), json!({ "replicas": "two" }))
+    .await;
+  assert!(
+    matches!(res, Err(Error::InvalidPatch { .. })),
+    "{:?}",
+    res.err()
+  );
+}
+
+#[tokio::test(start_paused = true)]
+async fn json_patches_props() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls, spec("app", 1)).await;
+
+  let patch = json!([{ "op": "replace", "path": "/image", "value": "web" }]);
+  let manifest = engine
+    .command()
+    .json_patch::<Deployment>("a".into(), patch)
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  assert_eq!(manifest.props, spec("web", 1));
+  assert_eq!(calls.take(), ["reconcile web x1"]);
+
+  let patch = json!([{ "op": "remove", "path": "/missing" }]);
+  let res = engine
+    .command()
+    .json_patch::<Deployment>("a".into(), patch)
+    .await;
+  assert!(
+    matches!(res, Err(Error::InvalidPatch { .. })),
+    "{:?}",
+    res.err()
+  );
+  assert_eq!(
+    engine.manifest::<Deployment>("a").unwrap().props.image,
+    "web"
+  );
+}
+
+#[tokio::test(start_paused = true)]
+async fn compares_patches_with_the_last_write() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls, spec("", 1)).await;
+  let command = engine.command();
+  assert_eq!(
+    engine.manifest::<Deployment>("a").unwrap().props.image,
+    "default"
+  );
+
+  // Same as the manifest last inserted, before admission filled the image.
+  let manifest = command
+    .patch::<Deployment>("a".into(), |props| props.image.clear())
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(manifest.meta.generation, 1);
+  assert!(calls.take().is_empty());
+
+  let manifest = command
+    .patch::<Deployment>("a".into(), |props| props.replicas = 2)
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(manifest.meta.generation, 2);
+  assert_eq!(calls.take(), ["reconcile default x2"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn conflicts_with_admission_during_patches() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls, spec("app", 1)).await;
+
+  // Admission patches the store directly, without a new generation.
+  let store = engine.store::<Deployment>();
+  let res = engine
+    .command()
+    .patch::<Deployment>("a".into(), move |props| {
+      store
+        .patch(manifest::<Deployment>("a", spec("admitted", 1)))
+        .unwrap();
+      props.replicas = 2;
+    })
+    .await;
+  engine.run_until_idle().await;
+
+  assert!(
+    matches!(res, Err(Error::Conflict { .. })),
+    "{:?}",
+    res.err()
+  );
+  let manifest = engine.manifest::<Deployment>("a").unwrap();
+  assert_eq!(manifest.props, spec("admitted", 1));
+  assert_eq!(manifest.meta.generation, 1);
+  assert!(calls.take().is_empty());
+}
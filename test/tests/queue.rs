@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{Command, Controller, ObjectDefinition, ObjectManifest};
+use gusto_engine::ControllerOptions;
+use gusto_test::TestEngine;
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+/// Milliseconds each reconcile takes.
+struct Job;
+impl ObjectDefinition for Job {
+  type Props = u64;
+}
+
+struct JobController {
+  calls: Calls,
+}
+
+#[async_trait::async_trait]
+impl Controller<Job> for JobController {
+  async fn initialize_state(&self, _: &ObjectManifest<Job>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Job>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    self.calls.push(manifest.name().to_string());
+    tokio::time::sleep(Duration::from_millis(manifest.props)).await;
+    Ok(None)
+  }
+}
+
+/// Reconciles `a`, `b` and `c` one at a time, then restarts the engine so
+/// that they're queued again as resyncs.
+async fn resync_storm(
+  calls: &Calls,
+  aging: Duration,
+  duration: u64,
+) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Job>();
+  let options = ControllerOptions::default()
+    .max_concurrent_reconciles(1)
+    .queue_aging(aging);
+  engine
+    .register_controller_with(
+      JobController {
+        calls: calls.clone(),
+      },
+      options,
+    )
+    .unwrap();
+  let faults = engine.engine_mut().faults();
+  engine.start();
+
+  let command = engine.command();
+  for name in ["a", "b", "c"] {
+    command
+      .insert_manifest(manifest::<Job>(name, duration))
+      .await
+      .unwrap();
+  }
+  engine.run_until_idle().await;
+  calls.take();
+
+  faults.restart();
+  tokio::time::sleep(Duration::from_millis(10)).await;
+
+  engine
+}
+
+#[tokio::test(start_paused = true)]
+async fn serves_changes_before_resyncs() {
+  let calls = Calls::default();
+  let mut engine = resync_storm(&calls, Duration::ZERO, 1000).await;
+
+  engine
+    .command()
+    .insert_manifest(manifest::<Job>("d", 1000))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  assert_eq!(calls.take(), ["a", "d", "b", "c"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn ages_waiting_resyncs_past_newer_changes() {
+  let calls = Calls::default();
+  let mut engine = resync_storm(&calls, Duration::from_secs(1), 5000).await;
+
+  tokio::time::sleep(Duration::from_secs(3)).await;
+  engine
+    .command()
+    .insert_manifest(manifest::<Job>("d", 1000))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  assert_eq!(calls.take(), ["a", "b", "c", "d"]);
+}
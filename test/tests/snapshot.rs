@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Controller, ObjectDefinition, ObjectKind, ObjectManifest, ObjectRef
+};
+use gusto_engine::{Owned, ReconcileState};
+use gusto_test::TestEngine;
+
+use self::common::manifest;
+
+mod common;
+
+struct App;
+impl ObjectDefinition for App {
+  type Props = u32;
+
+  fn kind() -> ObjectKind {
+    "app"
+  }
+}
+
+/// Has no controller.
+struct Volume;
+impl ObjectDefinition for Volume {
+  type Props = String;
+
+  fn kind() -> ObjectKind {
+    "volume"
+  }
+}
+
+struct AppController;
+
+#[async_trait::async_trait]
+impl Controller<App> for AppController {
+  async fn initialize_state(&self, _: &ObjectManifest<App>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    _: &ObjectManifest<App>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    Ok(None)
+  }
+}
+
+fn engine() -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<App>();
+  engine.register_object::<Volume>();
+  engine.register_controller(AppController).unwrap();
+  engine
+}
+
+async fn insert(engine: &mut TestEngine) {
+  let command = engine.command();
+  command
+    .insert_manifest(manifest::<App>("a", 1))
+    .await
+    .unwrap();
+  command
+    .insert_owned_manifest(
+      ObjectRef::new::<App>("a".into()),
+      manifest::<Volume>("a-data", "1Gi".to_owned()),
+    )
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn captures_registered_kinds() {
+  let mut engine = engine();
+
+  let snapshot = engine.engine_mut().snapshot();
+
+  let kinds = snapshot
+    .kinds
+    .iter()
+    .map(|k| (k.kind, k.controller, k.objects.len()))
+    .collect::<Vec<_>>();
+  assert_eq!(kinds, [("app", true, 0), ("volume", false, 0)]);
+  assert!(snapshot.owners.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn captures_objects_and_owners() {
+  let mut engine = engine();
+  engine.start();
+  insert(&mut engine).await;
+
+  let snapshot = engine.snapshot();
+
+  let app = &snapshot.kind("app").unwrap().objects[0];
+  assert_eq!(app.name, "a".into());
+  assert_eq!(app.props, "1");
+  assert!(app.id.is_some());
+  assert_eq!(app.reconcile, Some(ReconcileState::Idle));
+  assert_eq!(app.status.generation, 1);
+  assert_eq!(app.status.reconciled_generation, 1);
+  assert_eq!(app.owner, None);
+
+  let volume = &snapshot.kind("volume").unwrap().objects[0];
+  assert_eq!(volume.name, "a-data".into());
+  assert_eq!(volume.props, "\"1Gi\"");
+  assert_eq!(volume.id, None);
+  assert_eq!(volume.reconcile, None);
+  assert_eq!(volume.owner, Some(ObjectRef::new::<App>("a".into())));
+
+  assert_eq!(
+    snapshot.owners[&ObjectRef::new::<App>("a".into())],
+    [Owned {
+      kind: "volume",
+      name: "a-data".into(),
+    }]
+  );
+}
+
+#[tokio::test(start_paused = true)]
+async fn renders_owners_as_dot() {
+  let mut engine = engine();
+  engine.start();
+  insert(&mut engine).await;
+
+  assert_eq!(
+    engine.snapshot().to_dot(),
+    concat!(
+      "digraph owners {\n",
+      "  \"app/a\" [label=\"a\\napp\"];\n",
+      "  \"volume/a-data\" [label=\"a-data\\nvolume\"];\n",
+      "  \"app/a\" -> \"volume/a-data\";\n",
+      "}\n",
+    )
+  );
+}
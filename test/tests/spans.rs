@@ -0,0 +1,222 @@
+use std::{collections::BTreeMap, fmt::Debug, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Controller, ObjectDefinition, ObjectKind, ObjectManifest
+};
+use gusto_test::TestEngine;
+use parking_lot::Mutex;
+use tracing::{
+  field::{Field, Visit}, span::{Attributes, Id}, Event, Level, Subscriber
+};
+use tracing_subscriber::{
+  layer::{Context, SubscriberExt}, registry::LookupSpan, Layer
+};
+
+use self::common::manifest;
+
+mod common;
+
+/// Reconciles fail for zero.
+struct Job;
+impl ObjectDefinition for Job {
+  type Props = u32;
+
+  fn kind() -> ObjectKind {
+    "job"
+  }
+}
+
+struct JobController;
+
+#[async_trait::async_trait]
+impl Controller<Job> for JobController {
+  async fn initialize_state(&self, _: &ObjectManifest<Job>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Job>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    match manifest.props {
+      0 => anyhow::bail!("job failed"),
+      _ => Ok(None),
+    }
+  }
+}
+
+/// Fields
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Fields(BTreeMap<String, String>);
+
+impl Fields {
+  fn get(&self, name: &str) -> Option<&str> {
+    self.0.get(name).map(String::as_str)
+  }
+}
+
+impl Visit for Fields {
+  fn record_str(&mut self, field: &Field, value: &str) {
+    self.0.insert(field.name().to_owned(), value.to_owned());
+  }
+
+  fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+    self.0.insert(field.name().to_owned(), format!("{value:?}"));
+  }
+}
+
+/// Recorded
+#[derive(Debug)]
+enum Recorded {
+  Span(&'static str, Fields),
+  /// Warnings and errors, with the name and fields of their span.
+  Event(Level, Fields, Option<(&'static str, Fields)>),
+}
+
+/// Recorder
+///
+/// Records new spans, and warnings and errors along with their span.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<Recorded>>>);
+
+impl Recorder {
+  fn spans(&self, name: &str) -> Vec<Fields> {
+    self
+      .0
+      .lock()
+      .iter()
+      .filter_map(|recorded| {
+        match recorded {
+          Recorded::Span(span, fields) if *span == name => Some(fields.clone()),
+          _ => None,
+        }
+      })
+      .collect()
+  }
+
+  fn errors(&self) -> Vec<(Fields, Option<(&'static str, Fields)>)> {
+    self
+      .0
+      .lock()
+      .iter()
+      .filter_map(|recorded| {
+        match recorded {
+          Recorded::Event(Level::ERROR, fields, span) => {
+            Some((fields.clone(), span.clone()))
+          }
+          _ => None,
+        }
+      })
+      .collect()
+  }
+}
+
+impl<S> Layer<S> for Recorder
+where
+  S: Subscriber + for<'a> LookupSpan<'a>,
+{
+  fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+    let mut fields = Fields::default();
+    attrs.record(&mut fields);
+    if let Some(span) = ctx.span(id) {
+      span.extensions_mut().insert(fields.clone());
+    }
+    let name = attrs.metadata().name();
+    self.0.lock().push(Recorded::Span(name, fields));
+  }
+
+  fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+    let level = *event.metadata().level();
+    if level > Level::WARN {
+      return;
+    }
+
+    let mut fields = Fields::default();
+    event.record(&mut fields);
+    let span = ctx.event_span(event).map(|span| {
+      let span_fields = span.extensions().get::<Fields>().cloned();
+      (span.name(), span_fields.unwrap_or_default())
+    });
+    self.0.lock().push(Recorded::Event(level, fields, span));
+  }
+}
+
+fn engine() -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Job>();
+  engine.register_controller(JobController).unwrap();
+  engine.start();
+  engine
+}
+
+#[tokio::test(start_paused = true)]
+async fn spans_commands_admissions_and_reconciles() {
+  let recorder = Recorder::default();
+  let subscriber = tracing_subscriber::registry().with(recorder.clone());
+  let _guard = tracing::subscriber::set_default(subscriber);
+  let mut engine = engine();
+
+  engine
+    .command()
+    .insert_manifest(manifest::<Job>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  let fields = |span: &str| {
+    let spans = recorder.spans(span);
+    assert_eq!(spans.len(), 1, "{span} spans: {spans:?}");
+    spans.into_iter().next().unwrap()
+  };
+
+  let command = fields("command");
+  assert_eq!(command.get("action"), Some("InsertManifest"));
+  assert_eq!(command.get("kind"), Some("job"));
+  assert_eq!(command.get("name"), Some("a"));
+
+  let admission = fields("admission");
+  assert_eq!(admission.get("kind"), Some("job"));
+  assert_eq!(admission.get("name"), Some("a"));
+
+  let reconcile = fields("reconcile");
+  assert_eq!(reconcile.get("kind"), Some("job"));
+  assert_eq!(reconcile.get("name"), Some("a"));
+  assert_eq!(reconcile.get("attempt"), Some("1"));
+  assert!(reconcile.get("object_id").is_some(), "{reconcile:?}");
+  assert!(recorder.errors().is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn logs_controller_errors_in_their_reconcile() {
+  let recorder = Recorder::default();
+  let subscriber = tracing_subscriber::registry().with(recorder.clone());
+  let _guard = tracing::subscriber::set_default(subscriber);
+  let mut engine = engine();
+
+  engine
+    .command()
+    .insert_manifest(manifest::<Job>("a", 0))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  engine.advance(Duration::from_secs(1)).await;
+
+  let attempts = recorder
+    .errors()
+    .into_iter()
+    .map(|(fields, span)| {
+      let error = fields.get("error").unwrap_or_default().to_owned();
+      let (name, span) = span.expect("errors are logged within a span");
+      assert_eq!(name, "reconcile");
+      (span.get("attempt").unwrap().to_owned(), error)
+    })
+    .collect::<Vec<_>>();
+  assert!(attempts.len() >= 2, "{attempts:?}");
+  for (i, (attempt, error)) in attempts.iter().enumerate() {
+    assert_eq!(*attempt, (i + 1).to_string());
+    assert_eq!(error, "controller failed for 'a': job failed");
+  }
+}
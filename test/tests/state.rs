@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Controller, Error, ObjectDefinition, ObjectKind, ObjectManifest
+};
+use gusto_engine::ControllerOptions;
+use gusto_test::TestEngine;
+
+use self::common::manifest;
+
+mod common;
+
+/// Reconciles take as many seconds as the props, and count themselves in
+/// the state.
+struct Counter;
+impl ObjectDefinition for Counter {
+  type Props = u64;
+  type State = u32;
+
+  fn kind() -> ObjectKind {
+    "counter"
+  }
+}
+
+/// Same as [`Counter`], but its controller keeps its state private.
+struct Private;
+impl ObjectDefinition for Private {
+  type Props = u64;
+  type State = u32;
+
+  fn kind() -> ObjectKind {
+    "private"
+  }
+}
+
+struct CounterController;
+
+#[async_trait::async_trait]
+impl<O> Controller<O> for CounterController
+where
+  O: ObjectDefinition<Props = u64, State = u32>,
+{
+  async fn initialize_state(&self, _: &ObjectManifest<O>) -> Result<u32> {
+    Ok(0)
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<O>,
+    count: &mut u32,
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    *count += 1;
+    tokio::time::sleep(Duration::from_secs(manifest.props)).await;
+    Ok(None)
+  }
+}
+
+fn engine() -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Counter>();
+  engine.register_object::<Private>();
+  engine
+    .register_controller_with::<Counter>(
+      CounterController,
+      ControllerOptions::new().share_state(),
+    )
+    .unwrap();
+  engine
+    .register_controller::<Private>(CounterController)
+    .unwrap();
+  engine.start();
+  engine
+}
+
+#[tokio::test(start_paused = true)]
+async fn shares_state_after_each_reconcile() {
+  let mut engine = engine();
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Counter>("a", 0))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  let count = command
+    .inspect_state::<Counter, _>("a".into(), |c| *c)
+    .await;
+  assert_eq!(count.unwrap(), 1);
+
+  let mut state = command.watch_state::<Counter>("a".into()).await.unwrap();
+  assert_eq!(**state.borrow_and_update(), 1);
+
+  command
+    .insert_manifest(manifest::<Counter>("a", 1))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert!(state.has_changed().unwrap());
+  assert_eq!(**state.borrow_and_update(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn reads_state_without_waiting_for_reconciles() {
+  let mut engine = engine();
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Counter>("a", 0))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  // The next reconcile runs for a minute, while the state is read.
+  command
+    .insert_manifest(manifest::<Counter>("a", 60))
+    .await
+    .unwrap();
+  tokio::time::sleep(Duration::from_secs(1)).await;
+
+  let inspect = command.inspect_state::<Counter, _>("a".into(), |c| *c);
+  let count = tokio::time::timeout(Duration::from_secs(1), inspect).await;
+  assert_eq!(count.expect("state is read right away").unwrap(), 1);
+
+  engine.run_until_idle().await;
+  let count = command
+    .inspect_state::<Counter, _>("a".into(), |c| *c)
+    .await;
+  assert_eq!(count.unwrap(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn rejects_unshared_and_missing_states() {
+  let mut engine = engine();
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Private>("a", 0))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+
+  let res = command
+    .inspect_state::<Private, _>("a".into(), |c| *c)
+    .await;
+  assert!(
+    matches!(res, Err(Error::StateNotShared("private"))),
+    "{res:?}"
+  );
+
+  let res = command.watch_state::<Counter>("a".into()).await;
+  assert!(
+    matches!(res, Err(Error::NotFound { .. })),
+    "{:?}",
+    res.err()
+  );
+
+  command
+    .insert_manifest(manifest::<Counter>("a", 0))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  command
+    .remove_manifest::<Counter>("a".into())
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  let res = command
+    .inspect_state::<Counter, _>("a".into(), |c| *c)
+    .await;
+  assert!(matches!(res, Err(Error::NotFound { .. })), "{res:?}");
+}
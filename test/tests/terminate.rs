@@ -0,0 +1,124 @@
+use std::{
+  sync::{
+    atomic::{AtomicU32, Ordering}, Arc
+  }, time::Duration
+};
+
+use anyhow::Result;
+use gusto_core::{Command, Controller, ObjectDefinition, ObjectManifest};
+use gusto_test::TestEngine;
+
+use self::common::manifest;
+
+mod common;
+
+/// Attempts terminate fails before succeeding, if it ever does.
+struct Stuck;
+impl ObjectDefinition for Stuck {
+  type Props = Option<u32>;
+}
+
+#[derive(Clone, Default)]
+struct StuckController {
+  attempts: Arc<AtomicU32>,
+}
+
+#[async_trait::async_trait]
+impl Controller<Stuck> for StuckController {
+  async fn initialize_state(&self, _: &ObjectManifest<Stuck>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn terminate(
+    &self,
+    manifest: &ObjectManifest<Stuck>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    let attempts = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+    match manifest.props {
+      Some(failures) if attempts > failures => Ok(None),
+      _ => anyhow::bail!("stuck"),
+    }
+  }
+}
+
+fn engine(controller: &StuckController) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Stuck>();
+  engine.register_controller(controller.clone()).unwrap();
+  engine
+}
+
+async fn insert_and_remove(engine: &mut TestEngine, failures: Option<u32>) {
+  let command = engine.command();
+  command
+    .insert_manifest(manifest::<Stuck>("a", failures))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  command.remove_manifest::<Stuck>("a".into()).await.unwrap();
+  engine.run_until_idle().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn retries_failed_terminations() {
+  let controller = StuckController::default();
+  let mut engine = engine(&controller);
+  engine.start();
+
+  insert_and_remove(&mut engine, Some(2)).await;
+  assert_eq!(controller.attempts.load(Ordering::SeqCst), 1);
+
+  engine.advance(Duration::from_secs(60)).await;
+  engine.advance(Duration::from_secs(60)).await;
+  assert_eq!(controller.attempts.load(Ordering::SeqCst), 3);
+
+  let status = engine.command().watch_status::<Stuck>("a".into()).await;
+  assert!(status.is_err(), "status of 'a' wasn't released");
+}
+
+#[tokio::test(start_paused = true)]
+async fn abandons_terminations_that_keep_failing() {
+  let controller = StuckController::default();
+  let mut engine = engine(&controller);
+  let events = engine.engine_mut().events().subscribe();
+  engine.start();
+
+  insert_and_remove(&mut engine, None).await;
+  for _ in 0..30 {
+    engine.advance(Duration::from_secs(300)).await;
+  }
+
+  assert_eq!(controller.attempts.load(Ordering::SeqCst), 20);
+  assert!(
+    events
+      .try_iter()
+      .any(|event| event.reason == "TerminateAbandoned"),
+    "no TerminateAbandoned event"
+  );
+  let status = engine.command().watch_status::<Stuck>("a".into()).await;
+  assert!(status.is_err(), "status of 'a' wasn't released");
+}
+
+#[tokio::test(start_paused = true)]
+async fn unregistering_waits_for_terminations() {
+  let controller = StuckController::default();
+  let mut engine = engine(&controller);
+  engine.start();
+
+  insert_and_remove(&mut engine, Some(3)).await;
+  let handle = engine.handle();
+  let unregistered =
+    tokio::spawn(async move { handle.unregister_controller::<Stuck>().await });
+
+  // The engine isn't idle while unregistering, so move the clock directly.
+  tokio::time::sleep(Duration::from_millis(10)).await;
+  assert!(!unregistered.is_finished());
+  while !unregistered.is_finished() {
+    tokio::time::sleep(Duration::from_secs(1)).await;
+  }
+
+  unregistered.await.unwrap().unwrap();
+  assert_eq!(controller.attempts.load(Ordering::SeqCst), 4);
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{
+  Command, Controller, FieldChange, ObjectDefinition, ObjectManifest, PlannedChange
+};
+use gusto_test::TestEngine;
+
+use self::common::{manifest, Calls};
+
+mod common;
+
+/// Admission caps replicas at five.
+struct Replicas;
+impl ObjectDefinition for Replicas {
+  type Props = u32;
+
+  fn same_props(old: &u32, new: &u32) -> bool {
+    old == new
+  }
+}
+
+/// Doesn't compare its props, so every write goes through.
+struct Opaque;
+impl ObjectDefinition for Opaque {
+  type Props = u32;
+}
+
+struct ReplicasController {
+  calls: Calls,
+}
+
+#[async_trait::async_trait]
+impl Controller<Replicas> for ReplicasController {
+  async fn admit_manifest(
+    &self,
+    mut manifest: ObjectManifest<Replicas>,
+  ) -> Result<ObjectManifest<Replicas>> {
+    manifest.props = manifest.props.min(5);
+    Ok(manifest)
+  }
+
+  async fn initialize_state(&self, _: &ObjectManifest<Replicas>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Replicas>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    self.calls.push(format!("reconcile {}", manifest.props));
+    Ok(None)
+  }
+}
+
+struct OpaqueController {
+  calls: Calls,
+}
+
+#[async_trait::async_trait]
+impl Controller<Opaque> for OpaqueController {
+  async fn initialize_state(&self, _: &ObjectManifest<Opaque>) -> Result<()> {
+    Ok(())
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Opaque>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    self.calls.push(format!("reconcile {}", manifest.props));
+    Ok(None)
+  }
+}
+
+fn engine(calls: &Calls) -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Replicas>();
+  engine.register_object::<Opaque>();
+  engine
+    .register_controller(ReplicasController {
+      calls: calls.clone(),
+    })
+    .unwrap();
+  engine
+    .register_controller(OpaqueController {
+      calls: calls.clone(),
+    })
+    .unwrap();
+  engine.start();
+  engine
+}
+
+async fn generation<O>(engine: &TestEngine, name: &str) -> u64
+where
+  O: ObjectDefinition,
+{
+  let status = engine.command().watch_status::<O>(name.into()).await;
+  let generation = status.unwrap().borrow().generation;
+  generation
+}
+
+#[tokio::test(start_paused = true)]
+async fn skips_identical_writes() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls);
+  let command = engine.command();
+
+  for _ in 0..3 {
+    command
+      .insert_manifest(manifest::<Replicas>("a", 3))
+      .await
+      .unwrap();
+    engine.run_until_idle().await;
+  }
+
+  assert_eq!(calls.take(), ["reconcile 3"]);
+  assert_eq!(generation::<Replicas>(&engine, "a").await, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn compares_writes_before_admission() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls);
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Replicas>("a", 10))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert_eq!(calls.take(), ["reconcile 5"]);
+
+  let plan = command
+    .dry_run()
+    .insert_manifest(manifest::<Replicas>("a", 10))
+    .await
+    .unwrap();
+  assert_eq!(plan.change, PlannedChange::Unchanged);
+  assert!(plan.diff.is_none());
+
+  command
+    .insert_manifest(manifest::<Replicas>("a", 10))
+    .await
+    .unwrap();
+  engine.run_until_idle().await;
+  assert!(calls.take().is_empty());
+  assert_eq!(generation::<Replicas>(&engine, "a").await, 1);
+
+  // Diffs are against the stored props, as admitted.
+  let plan = command
+    .dry_run()
+    .insert_manifest(manifest::<Replicas>("a", 4))
+    .await
+    .unwrap();
+  assert_eq!(plan.change, PlannedChange::Update);
+  let diff = plan.diff.unwrap().iter().cloned().collect::<Vec<_>>();
+  assert_eq!(
+    diff,
+    [FieldChange {
+      path: String::new(),
+      old: Some("5".to_owned()),
+      new: Some("4".to_owned()),
+    }]
+  );
+}
+
+#[tokio::test(start_paused = true)]
+async fn writes_props_without_equality_every_time() {
+  let calls = Calls::default();
+  let mut engine = engine(&calls);
+  let command = engine.command();
+
+  for _ in 0..2 {
+    command
+      .insert_manifest(manifest::<Opaque>("a", 3))
+      .await
+      .unwrap();
+    engine.run_until_idle().await;
+  }
+
+  assert_eq!(calls.take(), ["reconcile 3", "reconcile 3"]);
+  assert_eq!(generation::<Opaque>(&engine, "a").await, 2);
+}
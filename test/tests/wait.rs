@@ -0,0 +1,277 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use gusto_core::{
+  Command, CommandEvent, CommandHandler, CommandReply, Condition, ConditionStatus, Controller, Error, ObjectDefinition, ObjectManifest
+};
+use gusto_test::TestEngine;
+
+use self::common::manifest;
+
+mod common;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Denied admission at zero and failing to initialize at one. Otherwise
+/// ready once reconciled, which, like terminating, takes a minute at three.
+struct Task;
+impl ObjectDefinition for Task {
+  type Props = u32;
+}
+
+/// Has no controller.
+struct Note;
+impl ObjectDefinition for Note {
+  type Props = ();
+}
+
+struct TaskController;
+
+#[async_trait::async_trait]
+impl Controller<Task> for TaskController {
+  async fn admit_manifest(
+    &self,
+    manifest: ObjectManifest<Task>,
+  ) -> Result<ObjectManifest<Task>> {
+    match manifest.props {
+      0 => anyhow::bail!("zero tasks"),
+      _ => Ok(manifest),
+    }
+  }
+
+  async fn initialize_state(
+    &self,
+    manifest: &ObjectManifest<Task>,
+  ) -> Result<()> {
+    match manifest.props {
+      1 => anyhow::bail!("no state"),
+      _ => Ok(()),
+    }
+  }
+
+  async fn reconcile(
+    &self,
+    manifest: &ObjectManifest<Task>,
+    _: &mut (),
+    command: &Command,
+  ) -> Result<Option<Duration>> {
+    slow(manifest).await;
+    let ready = Condition::new(Condition::READY, ConditionStatus::True);
+    command
+      .set_condition::<Task>(manifest.name().to_owned(), ready)
+      .await?;
+    Ok(None)
+  }
+
+  async fn terminate(
+    &self,
+    manifest: &ObjectManifest<Task>,
+    _: &mut (),
+    _: &Command,
+  ) -> Result<Option<Duration>> {
+    slow(manifest).await;
+    Ok(None)
+  }
+}
+
+async fn slow(manifest: &ObjectManifest<Task>) {
+  if manifest.props == 3 {
+    tokio::time::sleep(Duration::from_secs(60)).await;
+  }
+}
+
+/// Acknowledges every command without applying it.
+struct Acknowledge;
+impl CommandHandler for Acknowledge {
+  fn handle(&self, event: CommandEvent) {
+    if let Some(ack) = event.ack {
+      ack.send(Ok(CommandReply::Done)).ok();
+    }
+  }
+}
+
+fn engine() -> TestEngine {
+  let mut engine = TestEngine::new();
+  engine.register_object::<Task>();
+  engine.register_object::<Note>();
+  engine.register_controller(TaskController).unwrap();
+  engine.start();
+  engine
+}
+
+#[tokio::test(start_paused = true)]
+async fn waits_for_reconciliation() {
+  let engine = engine();
+
+  let res = engine
+    .command()
+    .insert_and_wait_reconciled(manifest::<Task>("a", 2), TIMEOUT)
+    .await;
+
+  assert!(res.is_ok(), "{res:?}");
+  let status = engine.store::<Task>().watch_status(&"a".into()).unwrap();
+  assert_eq!(status.borrow().reconciled_generation, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn fails_rejected_manifests() {
+  let engine = engine();
+  let command = engine.command();
+
+  let res = command
+    .insert_and_wait_reconciled(manifest::<Task>("a", 0), TIMEOUT)
+    .await;
+  assert!(matches!(res, Err(Error::AdmissionDenied { .. })), "{res:?}");
+
+  let res = command
+    .insert_and_wait_reconciled(manifest::<Task>("b", 1), TIMEOUT)
+    .await;
+  assert!(matches!(res, Err(Error::Controller { .. })), "{res:?}");
+
+  let res = command
+    .insert_and_wait_reconciled(manifest::<Note>("c", ()), TIMEOUT)
+    .await;
+  assert!(
+    matches!(res, Err(Error::ControllerNotRegistered(_))),
+    "{res:?}"
+  );
+}
+
+#[tokio::test(start_paused = true)]
+async fn times_out_waiting_for_reconciliation() {
+  let engine = engine();
+
+  let res = engine
+    .command()
+    .insert_and_wait_reconciled(manifest::<Task>("a", 3), TIMEOUT)
+    .await;
+
+  assert!(matches!(res, Err(Error::Timeout(TIMEOUT))), "{res:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn fails_unexpected_replies() {
+  let command = Command::with_handler(Acknowledge);
+
+  let res = command
+    .insert_and_wait_reconciled(manifest::<Task>("a", 2), TIMEOUT)
+    .await;
+
+  assert!(matches!(res, Err(Error::UnexpectedReply(_))), "{res:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn waits_for_conditions() {
+  let engine = engine();
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Task>("a", 2))
+    .await
+    .unwrap();
+  let conditions = command
+    .wait_for_condition::<Task>(
+      "a".into(),
+      |c| c.get(Condition::READY).is_some_and(Condition::is_true),
+      TIMEOUT,
+    )
+    .await
+    .unwrap();
+  assert!(conditions.get(Condition::ACCEPTED).unwrap().is_true());
+
+  // Rejections show up as conditions too.
+  command
+    .insert_manifest(manifest::<Task>("b", 0))
+    .await
+    .unwrap();
+  let conditions = command
+    .wait_for_condition::<Task>(
+      "b".into(),
+      |c| c.get(Condition::ACCEPTED).is_some(),
+      TIMEOUT,
+    )
+    .await
+    .unwrap();
+  let accepted = conditions.get(Condition::ACCEPTED).unwrap();
+  assert!(accepted.is_false());
+  assert_eq!(accepted.reason, "AdmissionDenied");
+}
+
+#[tokio::test(start_paused = true)]
+async fn fails_waiting_for_conditions_never_met() {
+  let mut engine = engine();
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Task>("a", 3))
+    .await
+    .unwrap();
+  let res = command
+    .wait_for_condition::<Task>(
+      "a".into(),
+      |c| c.get(Condition::READY).is_some(),
+      TIMEOUT,
+    )
+    .await;
+  assert!(matches!(res, Err(Error::Timeout(TIMEOUT))), "{res:?}");
+
+  let res = command
+    .wait_for_condition::<Task>("b".into(), |_| false, TIMEOUT)
+    .await;
+  assert!(matches!(res, Err(Error::NotFound { .. })), "{res:?}");
+
+  // Objects removed meanwhile are no longer found.
+  command
+    .insert_manifest(manifest::<Task>("c", 2))
+    .await
+    .unwrap();
+  let waiting = command.clone();
+  let res = tokio::spawn(async move {
+    waiting
+      .wait_for_condition::<Task>("c".into(), |_| false, TIMEOUT)
+      .await
+  });
+  command.remove_manifest::<Task>("c".into()).await.unwrap();
+  engine.run_until_idle().await;
+  let res = res.await.unwrap();
+  assert!(matches!(res, Err(Error::NotFound { .. })), "{res:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn waits_for_deletion() {
+  let mut engine = engine();
+  let command = engine.command();
+
+  command
+    .insert_and_wait_reconciled(manifest::<Task>("a", 2), TIMEOUT)
+    .await
+    .unwrap();
+  let waiting = command.clone();
+  let res = tokio::spawn(async move {
+    waiting.wait_for_deletion::<Task>("a".into(), TIMEOUT).await
+  });
+  command.remove_manifest::<Task>("a".into()).await.unwrap();
+  engine.run_until_idle().await;
+
+  assert!(res.await.unwrap().is_ok());
+  engine.assert_absent::<Task>("a");
+
+  let res = command.wait_for_deletion::<Task>("b".into(), TIMEOUT).await;
+  assert!(res.is_ok(), "{res:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn times_out_waiting_for_termination() {
+  let mut engine = engine();
+  let command = engine.command();
+
+  command
+    .insert_manifest(manifest::<Task>("a", 3))
+    .await
+    .unwrap();
+  engine.advance(Duration::from_secs(60)).await;
+  command.remove_manifest::<Task>("a".into()).await.unwrap();
+
+  let res = command.wait_for_deletion::<Task>("a".into(), TIMEOUT).await;
+  assert!(matches!(res, Err(Error::Timeout(TIMEOUT))), "{res:?}");
+}